@@ -1,11 +1,12 @@
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::io::Read;
 
 fn part1(input: &mut dyn Read) -> u32 {
-    BufReader::new(input).lines().count() as u32
+    reader::lines(input).count() as u32
 }
 
 fn part2(input: &mut dyn Read) -> u32 {
-    BufReader::new(input).lines().count() as u32
+    reader::lines(input).count() as u32
 }
 
 pub fn run_part1(input: &mut dyn Read) {
@@ -1,62 +1,293 @@
+use crate::reader;
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read};
+use std::io::Read;
 
-fn read_line(line: &str) -> (u32, u32) {
-    let mut pieces = line.split_whitespace();
-    let l = pieces.next().unwrap().parse::<u32>().unwrap();
-    let r = pieces.next().unwrap().parse::<u32>().unwrap();
-    (l, r)
+const TOP: usize = 5;
+
+#[derive(Debug, PartialEq)]
+struct BadLine {
+    line: usize,
+    expected: usize,
+    text: String,
 }
 
-fn load(input: &mut dyn Read) -> (Vec<u32>, Vec<u32>) {
-    BufReader::new(input)
-        .lines()
-        .map(|l| read_line(&l.unwrap().to_string()))
-        .unzip()
+impl std::fmt::Display for BadLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {} should have {} location IDs: {:?}",
+            self.line, self.expected, self.text
+        )
+    }
 }
 
-fn part1(input: &mut dyn Read) -> u32 {
-    let (left, right) = {
-        let (mut left, mut right) = load(input);
+fn read_row(line: &str) -> Option<Vec<u32>> {
+    line.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse::<u32>().ok())
+        .collect()
+}
 
-        left.sort();
-        right.sort();
+fn read_line(line: &str) -> Option<(u32, u32)> {
+    match read_row(line)?.as_slice() {
+        &[l, r] => Some((l, r)),
+        _ => None,
+    }
+}
 
-        (left, right)
-    };
+fn pairs(input: &mut dyn Read) -> impl Iterator<Item = Result<(u32, u32), BadLine>> + '_ {
+    reader::lines(input).enumerate().map(|(n, text)| {
+        read_line(&text).ok_or(BadLine {
+            line: n + 1,
+            expected: 2,
+            text,
+        })
+    })
+}
 
-    left.into_iter()
-        .zip(right.into_iter())
-        .map(|(a, b)| a.abs_diff(b))
-        .sum()
+fn load_columns(input: &mut dyn Read) -> Result<Vec<Vec<u32>>, BadLine> {
+    reader::lines(input)
+        .enumerate()
+        .try_fold(Vec::<Vec<u32>>::new(), |mut columns, (n, text)| {
+            let expected = if columns.is_empty() { 2 } else { columns.len() };
+            match read_row(&text) {
+                Some(row) if columns.is_empty() && row.len() >= 2 => {
+                    columns = vec![Vec::new(); row.len()];
+                    columns.iter_mut().zip(row).for_each(|(c, v)| c.push(v));
+                    Ok(columns)
+                }
+                Some(row) if row.len() == expected => {
+                    columns.iter_mut().zip(row).for_each(|(c, v)| c.push(v));
+                    Ok(columns)
+                }
+                _ => Err(BadLine {
+                    line: n + 1,
+                    expected,
+                    text,
+                }),
+            }
+        })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pairing {
+    Pairwise,
+    Reference(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    Absolute,
+    Squared,
+    Max,
+}
+
+impl Metric {
+    fn apply(self, a: &[u32], b: &[u32]) -> u64 {
+        let diffs = a.iter().zip(b).map(|(x, y)| x.abs_diff(*y) as u64);
+        match self {
+            Metric::Absolute => diffs.sum(),
+            Metric::Squared => diffs.map(|d| d * d).sum(),
+            Metric::Max => diffs.max().unwrap_or(0),
+        }
+    }
+}
+
+fn column_pairs(width: usize, pairing: Pairing) -> Vec<(usize, usize)> {
+    match pairing {
+        Pairing::Pairwise => (0..width)
+            .flat_map(|a| (a + 1..width).map(move |b| (a, b)))
+            .collect(),
+        Pairing::Reference(r) => {
+            assert!(r < width, "reference column {r} is out of range");
+            (0..width).filter(|&c| c != r).map(|c| (r, c)).collect()
+        }
+    }
+}
+
+fn distance(columns: &[Vec<u32>], pairing: Pairing, metric: Metric) -> u64 {
+    let sorted = columns
+        .iter()
+        .map(|c| {
+            let mut c = c.clone();
+            c.sort();
+            c
+        })
+        .collect::<Vec<Vec<u32>>>();
+
+    let per_pair = column_pairs(sorted.len(), pairing)
+        .into_iter()
+        .map(|(a, b)| metric.apply(&sorted[a], &sorted[b]));
+    match metric {
+        Metric::Max => per_pair.max().unwrap_or(0),
+        Metric::Absolute | Metric::Squared => per_pair.sum(),
+    }
+}
+
+fn load(input: &mut dyn Read) -> Result<(Vec<u32>, Vec<u32>), BadLine> {
+    pairs(input).collect()
+}
+
+fn part1(input: &mut dyn Read) -> Result<u64, BadLine> {
+    let (left, right) = load(input)?;
+    Ok(distance(&[left, right], Pairing::Pairwise, Metric::Absolute))
 }
 
 fn counts(nums: &[u32]) -> HashMap<u32, u32> {
-    nums.into_iter().fold(HashMap::new(), |mut cts, n| {
+    nums.iter().fold(HashMap::new(), |mut cts, n| {
         let ct = cts.get(n).unwrap_or(&0) + 1;
         cts.insert(*n, ct);
         cts
     })
 }
 
-fn part2(input: &mut dyn Read) -> u32 {
-    let (left, right) = {
-        let (left, right) = load(input);
-
-        (counts(&left), counts(&right))
-    };
+fn similarity(left: &[u32], right: &[u32]) -> u64 {
+    let (left, right) = (counts(left), counts(right));
 
     left.into_iter()
-        .map(|(num, ct)| num * ct * right.get(&num).unwrap_or(&0))
+        .map(|(num, ct)| num as u64 * ct as u64 * *right.get(&num).unwrap_or(&0) as u64)
         .sum()
 }
 
+fn part2(input: &mut dyn Read) -> Result<u64, BadLine> {
+    let (left, right) = load(input)?;
+    Ok(similarity(&left, &right))
+}
+
+#[derive(Debug, PartialEq)]
+struct ListStats {
+    min: u32,
+    max: u32,
+    median: f64,
+    distinct: usize,
+}
+
+fn list_stats(sorted: &[u32]) -> Option<ListStats> {
+    let mid = sorted.len() / 2;
+    let median = match sorted.len() % 2 {
+        0 => (*sorted.get(mid.checked_sub(1)?)? as f64 + sorted[mid] as f64) / 2.0,
+        _ => sorted[mid] as f64,
+    };
+
+    Some(ListStats {
+        min: *sorted.first()?,
+        max: *sorted.last()?,
+        median,
+        distinct: counts(sorted).len(),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Stats {
+    left: Option<ListStats>,
+    right: Option<ListStats>,
+    distinct: usize,
+    widest: Vec<(u32, u32, u32)>,
+    contributors: Vec<(u32, u64)>,
+}
+
+fn stats(mut left: Vec<u32>, mut right: Vec<u32>) -> Stats {
+    left.sort();
+    right.sort();
+
+    let mut widest = left
+        .iter()
+        .zip(right.iter())
+        .map(|(&a, &b)| (a, b, a.abs_diff(b)))
+        .collect::<Vec<(u32, u32, u32)>>();
+    widest.sort_by_key(|&(a, b, dist)| (std::cmp::Reverse(dist), a, b));
+    widest.truncate(TOP);
+
+    let right_counts = counts(&right);
+    let mut contributors = counts(&left)
+        .into_iter()
+        .map(|(num, ct)| {
+            let score = num as u64 * ct as u64 * *right_counts.get(&num).unwrap_or(&0) as u64;
+            (num, score)
+        })
+        .filter(|&(_, score)| score > 0)
+        .collect::<Vec<(u32, u64)>>();
+    contributors.sort_by_key(|&(num, score)| (std::cmp::Reverse(score), num));
+    contributors.truncate(TOP);
+
+    Stats {
+        left: list_stats(&left),
+        right: list_stats(&right),
+        distinct: counts(&[left, right].concat()).len(),
+        widest,
+        contributors,
+    }
+}
+
 pub fn run_part1(input: &mut dyn Read) {
-    println!("{}", part1(input));
+    match part1(input) {
+        Ok(distance) => println!("{}", distance),
+        Err(e) => println!("error: {}", e),
+    }
 }
 
 pub fn run_part2(input: &mut dyn Read) {
-    println!("{}", part2(input));
+    match part2(input) {
+        Ok(similarity) => println!("{}", similarity),
+        Err(e) => println!("error: {}", e),
+    }
+}
+
+pub fn run_stats(input: &mut dyn Read) {
+    let (left, right): (Vec<u32>, Vec<u32>) = pairs(input)
+        .filter_map(|p| p.map_err(|e| eprintln!("skipped: {}", e)).ok())
+        .unzip();
+    let stats = stats(left, right);
+
+    for (name, list) in [("left", &stats.left), ("right", &stats.right)] {
+        match list {
+            Some(l) => println!(
+                "{name}: min {}, max {}, median {}, {} distinct",
+                l.min, l.max, l.median, l.distinct
+            ),
+            None => println!("{name}: empty"),
+        }
+    }
+    println!("{} distinct IDs in total", stats.distinct);
+
+    println!("largest distances:");
+    for (a, b, dist) in stats.widest {
+        println!("    {a} - {b} = {dist}");
+    }
+    println!("top similarity contributors:");
+    for (num, score) in stats.contributors {
+        println!("    {num}: {score}");
+    }
+}
+
+pub fn run_columns(input: &mut dyn Read, args: &[String]) {
+    let value = |key: &str| args.iter().find_map(|a| a.strip_prefix(key));
+    let pairing = match value("ref=") {
+        Some(r) => Pairing::Reference(r.parse().expect("ref should be a column number")),
+        None => Pairing::Pairwise,
+    };
+    let metric = match value("metric=").unwrap_or("abs") {
+        "abs" => Metric::Absolute,
+        "sq" => Metric::Squared,
+        "max" => Metric::Max,
+        m => panic!("metric should be abs, sq or max, got {m}"),
+    };
+    let columns = match load_columns(input) {
+        Ok(columns) => columns,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        }
+    };
+
+    println!("{}", distance(&columns, pairing, metric));
+    if let Some(pair) = value("similarity=") {
+        let (a, b) = pair
+            .split_once(',')
+            .map(|(a, b)| (a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap()))
+            .expect("similarity should be two column numbers, like 0,1");
+        println!("{}", similarity(&columns[a], &columns[b]));
+    }
 }
 
 #[cfg(test)]
@@ -67,36 +298,154 @@ mod tests {
     #[test]
     fn test_read_line() {
         let line = "3   4";
-        let (left, right) = read_line(line);
+        let (left, right) = read_line(line).unwrap();
         assert_eq!(left, 3);
         assert_eq!(right, 4);
+
+        assert_eq!(read_line("3,4"), Some((3, 4)));
+        assert_eq!(read_line("3\t4"), Some((3, 4)));
+        assert_eq!(read_line("3, 4"), Some((3, 4)));
+        assert_eq!(read_line("3"), None);
+        assert_eq!(read_line("3 4 5"), None);
+        assert_eq!(read_line("3 x"), None);
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        let mut data = "1 2\n3,4\nfive 6\n\n7\t8\n".as_bytes();
+        let results = pairs(&mut data).collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                Ok((1, 2)),
+                Ok((3, 4)),
+                Err(BadLine {
+                    line: 3,
+                    expected: 2,
+                    text: String::from("five 6")
+                }),
+                Err(BadLine {
+                    line: 4,
+                    expected: 2,
+                    text: String::new()
+                }),
+                Ok((7, 8)),
+            ]
+        );
+
+        let mut data = "1 2\nfive 6\n".as_bytes();
+        assert_eq!(
+            part1(&mut data).unwrap_err().to_string(),
+            "line 2 should have 2 location IDs: \"five 6\""
+        );
+        let mut data = "1 2 3\n".as_bytes();
+        assert!(part2(&mut data).is_err());
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut data = "1 5 2\n4,1,3\n2\t3 9\n".as_bytes();
+        let columns = load_columns(&mut data).unwrap();
+        assert_eq!(columns, vec![vec![1, 4, 2], vec![5, 1, 3], vec![2, 3, 9]]);
+
+        // Sorted: [1, 2, 4], [1, 3, 5], [2, 3, 9]
+        assert_eq!(distance(&columns, Pairing::Pairwise, Metric::Absolute), 14);
+        assert_eq!(distance(&columns, Pairing::Pairwise, Metric::Squared), 46);
+        assert_eq!(distance(&columns, Pairing::Pairwise, Metric::Max), 5);
+        assert_eq!(
+            distance(&columns, Pairing::Reference(2), Metric::Absolute),
+            12
+        );
+        assert_eq!(distance(&columns, Pairing::Reference(0), Metric::Max), 5);
+
+        assert_eq!(similarity(&columns[0], &columns[1]), 1);
+        assert_eq!(similarity(&columns[2], &columns[0]), 2);
+
+        let mut ragged = "1 2 3\n4 5\n".as_bytes();
+        assert_eq!(
+            load_columns(&mut ragged),
+            Err(BadLine {
+                line: 2,
+                expected: 3,
+                text: String::from("4 5")
+            })
+        );
+    }
+
+    #[test]
+    fn test_two_columns_match_parts() {
+        let mut f = File::open("input/d01-f.txt").unwrap();
+        let columns = load_columns(&mut f).unwrap();
+        assert_eq!(
+            distance(&columns, Pairing::Reference(0), Metric::Absolute),
+            1197984
+        );
+        assert_eq!(similarity(&columns[0], &columns[1]), 23387399);
+    }
+
+    #[test]
+    fn test_stats_example() {
+        let (left, right) = load(&mut File::open("input/d01-e.txt").unwrap()).unwrap();
+        let s = stats(left, right);
+
+        assert_eq!(
+            s.left,
+            Some(ListStats {
+                min: 1,
+                max: 4,
+                median: 3.0,
+                distinct: 4
+            })
+        );
+        assert_eq!(
+            s.right,
+            Some(ListStats {
+                min: 3,
+                max: 9,
+                median: 3.5,
+                distinct: 4
+            })
+        );
+        assert_eq!(s.distinct, 6);
+        assert_eq!(
+            s.widest,
+            vec![(4, 9, 5), (1, 3, 2), (3, 5, 2), (2, 3, 1), (3, 4, 1)]
+        );
+        assert_eq!(s.contributors, vec![(3, 27), (4, 4)]);
+        assert_eq!(s.contributors.iter().map(|c| c.1).sum::<u64>(), 31);
+
+        let many = vec![90000; 70000];
+        let s = stats(many.clone(), many);
+        assert_eq!(s.contributors, vec![(90000, 441000000000000)]);
+
+        assert_eq!(stats(vec![], vec![]).left, None);
     }
 
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d01-e.txt").unwrap();
         let dist = part1(&mut f);
-        assert_eq!(dist, 11);
+        assert_eq!(dist, Ok(11));
     }
 
     #[test]
     fn test_part1_full() {
         let mut f = File::open("input/d01-f.txt").unwrap();
         let dist = part1(&mut f);
-        assert_eq!(dist, 1197984);
+        assert_eq!(dist, Ok(1197984));
     }
 
     #[test]
     fn test_part2_example() {
         let mut f = File::open("input/d01-e.txt").unwrap();
         let dist = part2(&mut f);
-        assert_eq!(dist, 31);
+        assert_eq!(dist, Ok(31));
     }
 
     #[test]
     fn test_part2_full() {
         let mut f = File::open("input/d01-f.txt").unwrap();
         let dist = part2(&mut f);
-        assert_eq!(dist, 23387399);
+        assert_eq!(dist, Ok(23387399));
     }
 }
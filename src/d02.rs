@@ -1,5 +1,100 @@
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::io::Read;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Either,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Policy {
+    min_step: u32,
+    max_step: u32,
+    allow_plateaus: bool,
+    direction: Direction,
+    max_bad_levels: usize,
+}
+
+impl Policy {
+    const PART1: Policy = Policy {
+        min_step: 1,
+        max_step: 3,
+        allow_plateaus: false,
+        direction: Direction::Either,
+        max_bad_levels: 0,
+    };
+
+    const PART2: Policy = Policy {
+        max_bad_levels: 1,
+        ..Policy::PART1
+    };
+
+    fn from_args(args: &[String]) -> Policy {
+        let settings = args.iter().filter_map(|a| a.split_once('='));
+
+        // The preset is the starting point wherever it appears, so that the
+        // other settings can change it
+        let preset = match settings.clone().rfind(|&(key, _)| key == "preset") {
+            None | Some((_, "p1")) => Policy::PART1,
+            Some((_, "p2")) => Policy::PART2,
+            Some((_, value)) => panic!("preset should be p1 or p2, got {value}"),
+        };
+
+        let policy = settings
+            .filter(|&(key, _)| key != "preset")
+            .fold(preset, |policy, (key, value)| match key {
+                "min" => Policy {
+                    min_step: value.parse().expect("min should be a number"),
+                    ..policy
+                },
+                "max" => Policy {
+                    max_step: value.parse().expect("max should be a number"),
+                    ..policy
+                },
+                "plateaus" => Policy {
+                    allow_plateaus: match value {
+                        "yes" => true,
+                        "no" => false,
+                        _ => panic!("plateaus should be yes or no, got {value}"),
+                    },
+                    ..policy
+                },
+                "dir" => Policy {
+                    direction: match value {
+                        "up" => Direction::Up,
+                        "down" => Direction::Down,
+                        "either" => Direction::Either,
+                        _ => panic!("dir should be up, down or either, got {value}"),
+                    },
+                    ..policy
+                },
+                "bad" => Policy {
+                    max_bad_levels: value.parse().expect("bad should be a number"),
+                    ..policy
+                },
+                _ => panic!("unknown policy setting \"{key}\""),
+            });
+
+        assert!(
+            policy.min_step <= policy.max_step,
+            "min should not be more than max"
+        );
+
+        policy
+    }
+
+    fn safe_distance(&self, a: u32, b: u32) -> bool {
+        (self.min_step..=self.max_step).contains(&a.abs_diff(b))
+    }
+
+    fn allows(&self, direction: Direction) -> bool {
+        self.direction == Direction::Either || self.direction == direction
+    }
+}
+
+#[derive(Clone, Copy)]
 enum State {
     Empty,
     One(u32),
@@ -8,76 +103,288 @@ enum State {
     Unsafe,
 }
 
-fn safe_distance(a: u32, b: u32) -> bool {
-    const OK_DISTANCES: &[u32] = &[1, 2, 3];
-    OK_DISTANCES.contains(&a.abs_diff(b))
+fn step(policy: &Policy, state: State, level: u32) -> State {
+    let plateau = |prev: u32| policy.allow_plateaus && prev == level;
+    let up = |prev: u32| prev < level && policy.safe_distance(prev, level);
+    let down = |prev: u32| prev > level && policy.safe_distance(prev, level);
+
+    match state {
+        State::Empty => State::One(level),
+        State::One(prev) if plateau(prev) => State::One(level),
+        State::One(prev) if up(prev) && policy.allows(Direction::Up) => State::Up(level),
+        State::One(prev) if down(prev) && policy.allows(Direction::Down) => State::Down(level),
+        State::Up(prev) if up(prev) || plateau(prev) => State::Up(level),
+        State::Down(prev) if down(prev) || plateau(prev) => State::Down(level),
+        State::Unsafe => State::Unsafe,
+        _ => State::Unsafe,
+    }
 }
 
-fn safe_step(lower: u32, higher: u32) -> bool {
-    safe_distance(lower, higher) && lower < higher
+/// A report is only safe once it has settled on a direction, so reports
+/// with fewer than two levels never are
+fn safe_report(policy: &Policy, levels: &[u32]) -> bool {
+    matches!(
+        levels
+            .iter()
+            .copied()
+            .fold(State::Empty, |state, level| step(policy, state, level)),
+        State::Up(_) | State::Down(_)
+    )
 }
 
-fn safe_report(levels: &[u32]) -> bool {
-    match levels
-        .iter()
-        .copied()
-        .fold(State::Empty, |state, level| match state {
-            State::Empty => State::One(level),
-            State::One(prev) => match (prev < level, safe_distance(prev, level)) {
-                (false, true) => State::Down(level),
-                (true, true) => State::Up(level),
-                _ => State::Unsafe,
-            },
-            State::Up(prev) => {
-                if safe_step(prev, level) {
-                    State::Up(level)
-                } else {
-                    State::Unsafe
-                }
+fn slot(state: &State) -> Option<usize> {
+    match state {
+        State::One(_) => Some(0),
+        State::Up(_) => Some(1),
+        State::Down(_) => Some(2),
+        _ => None,
+    }
+}
+
+/// The fewest removals that leave a safe series ending at some kept level,
+/// and the kept level and direction slot before it
+#[derive(Clone, Copy)]
+struct Kept {
+    removed: usize,
+    prev: Option<(usize, usize)>,
+}
+
+/// Scratch space for checking reports with bad levels removed, kept between
+/// reports so that checking each one does not allocate
+struct Dampener {
+    fewest: Vec<[Option<Kept>; 3]>,
+}
+
+impl Dampener {
+    fn new() -> Dampener {
+        Dampener { fewest: Vec::new() }
+    }
+
+    /// Finds the kept level and direction slot that a safe series with the
+    /// fewest removals ends at
+    fn best_end(&mut self, policy: &Policy, levels: &[u32]) -> Option<(usize, usize)> {
+        let k = policy.max_bad_levels;
+
+        // Indexed by the direction slot of the final state. Only the k + 1
+        // levels before a level can precede it.
+        self.fewest.clear();
+        self.fewest.resize(levels.len(), [None; 3]);
+
+        for (pos, &level) in levels.iter().enumerate() {
+            let mut best = [None::<Kept>; 3];
+            if pos <= k {
+                best[0] = Some(Kept {
+                    removed: pos,
+                    prev: None,
+                });
             }
-            State::Down(prev) => {
-                if safe_step(level, prev) {
-                    State::Down(level)
-                } else {
-                    State::Unsafe
+
+            let first = pos.saturating_sub(k + 1);
+            for (prev, (fewest, &prev_level)) in self
+                .fewest
+                .iter()
+                .zip(levels)
+                .enumerate()
+                .take(pos)
+                .skip(first)
+            {
+                let skipped = pos - prev - 1;
+                for (dir, kept) in fewest.iter().enumerate() {
+                    let Some(removed) = kept.map(|k| k.removed + skipped).filter(|&r| r <= k)
+                    else {
+                        continue;
+                    };
+                    let state = match dir {
+                        0 => State::One(prev_level),
+                        1 => State::Up(prev_level),
+                        _ => State::Down(prev_level),
+                    };
+                    // On a tie the nearer level wins, so the earlier of two
+                    // equally good levels is the one removed
+                    if let Some(s) = slot(&step(policy, state, level)) {
+                        if best[s].is_none_or(|b| removed <= b.removed) {
+                            best[s] = Some(Kept {
+                                removed,
+                                prev: Some((prev, dir)),
+                            });
+                        }
+                    }
                 }
             }
-            a => a,
-        }) {
-        State::Up(_) => true,
-        State::Down(_) => true,
-        _ => false,
+
+            self.fewest[pos] = best;
+        }
+
+        let len = levels.len();
+        (len.saturating_sub(k + 1)..len)
+            .flat_map(|pos| {
+                self.fewest[pos]
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .filter_map(move |(s, kept)| kept.map(|kept| (pos, s, kept.removed)))
+            })
+            .map(|(pos, s, removed)| (removed + (len - 1 - pos), pos, s))
+            .filter(|&(total, _, _)| total <= k)
+            .min()
+            .map(|(_, pos, s)| (pos, s))
+    }
+
+    fn is_safe(&mut self, policy: &Policy, levels: &[u32]) -> bool {
+        if policy.max_bad_levels == 0 {
+            return safe_report(policy, levels);
+        }
+        self.best_end(policy, levels).is_some()
+    }
+
+    /// Positions of the fewest levels to remove to make the report safe
+    fn removals(&mut self, policy: &Policy, levels: &[u32]) -> Option<Vec<usize>> {
+        let (mut pos, mut s) = self.best_end(policy, levels)?;
+        let mut kept = vec![false; levels.len()];
+        loop {
+            kept[pos] = true;
+            match self.fewest[pos][s].and_then(|k| k.prev) {
+                Some((prev, prev_slot)) => (pos, s) = (prev, prev_slot),
+                None => break,
+            }
+        }
+        Some((0..levels.len()).filter(|&pos| !kept[pos]).collect())
     }
 }
 
-fn safe_report_v2(levels: &[u32]) -> bool {
-    if safe_report(levels) {
-        return true;
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Reason {
+    WrongDirection,
+    TooLarge,
+    TooSmall,
+    Plateau,
+}
+
+impl Reason {
+    fn describe(self) -> &'static str {
+        match self {
+            Reason::WrongDirection => "wrong direction",
+            Reason::TooLarge => "step too large",
+            Reason::TooSmall => "step too small",
+            Reason::Plateau => "plateau",
+        }
     }
+}
 
-    (0..levels.len())
-        .map(|pos| {
-            levels
-                .iter()
-                .copied()
-                .take(pos)
-                .chain(levels.iter().copied().skip(pos + 1))
-                .collect::<Vec<u32>>()
+#[derive(Debug, PartialEq)]
+struct Offence {
+    pos: usize,
+    prev: u32,
+    level: u32,
+    reason: Reason,
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Safe,
+    SafeWithout(Vec<usize>),
+    Unsafe(Offence),
+    NoDirection,
+}
+
+fn reason(policy: &Policy, state: &State, level: u32) -> Reason {
+    let (prev, established) = match *state {
+        State::One(prev) => (prev, Direction::Either),
+        State::Up(prev) => (prev, Direction::Up),
+        State::Down(prev) => (prev, Direction::Down),
+        State::Empty | State::Unsafe => panic!("only a level can be unsafe to step from"),
+    };
+    let direction = if level > prev {
+        Direction::Up
+    } else {
+        Direction::Down
+    };
+
+    if prev == level {
+        Reason::Plateau
+    } else if !policy.allows(direction)
+        || (established != Direction::Either && established != direction)
+    {
+        Reason::WrongDirection
+    } else if prev.abs_diff(level) > policy.max_step {
+        Reason::TooLarge
+    } else {
+        Reason::TooSmall
+    }
+}
+
+fn first_offence(policy: &Policy, levels: &[u32]) -> Option<Offence> {
+    levels
+        .iter()
+        .copied()
+        .enumerate()
+        .try_fold(State::Empty, |state, (pos, level)| {
+            match step(policy, state, level) {
+                State::Unsafe => {
+                    let (State::One(prev) | State::Up(prev) | State::Down(prev)) = state else {
+                        unreachable!()
+                    };
+                    Err(Offence {
+                        pos,
+                        prev,
+                        level,
+                        reason: reason(policy, &state, level),
+                    })
+                }
+                next => Ok(next),
+            }
         })
-        .any(|reduced_levels| safe_report(&reduced_levels))
+        .err()
+}
+
+fn explain(policy: &Policy, levels: &[u32]) -> Verdict {
+    match Dampener::new().removals(policy, levels) {
+        Some(removed) if removed.is_empty() => Verdict::Safe,
+        Some(removed) => Verdict::SafeWithout(removed),
+        None => match first_offence(policy, levels) {
+            Some(offence) => Verdict::Unsafe(offence),
+            None => Verdict::NoDirection,
+        },
+    }
 }
 
-fn part(f: fn(&[u32]) -> bool, input: &mut dyn Read) -> u32 {
-    BufReader::new(input)
-        .lines()
+fn verdict_json(line: usize, verdict: &Verdict) -> String {
+    match verdict {
+        Verdict::Safe => format!("{{\"line\":{line},\"verdict\":\"safe\"}}"),
+        Verdict::SafeWithout(removed) => format!(
+            "{{\"line\":{line},\"verdict\":\"dampened\",\"removed\":[{}]}}",
+            removed
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+        Verdict::Unsafe(o) => format!(
+            "{{\"line\":{line},\"verdict\":\"unsafe\",\"index\":{},\"pair\":[{},{}],\"reason\":\"{}\"}}",
+            o.pos,
+            o.prev,
+            o.level,
+            o.reason.describe()
+        ),
+        Verdict::NoDirection => {
+            format!("{{\"line\":{line},\"verdict\":\"unsafe\",\"reason\":\"no direction\"}}")
+        }
+    }
+}
+
+fn read_report(line: &str) -> Vec<u32> {
+    line.split_whitespace()
+        .map(|s| s.parse::<u32>().unwrap())
+        .collect()
+}
+
+fn part(policy: &Policy, input: &mut dyn Read) -> u32 {
+    let mut dampener = Dampener::new();
+    reader::lines(input)
         .map(|l| {
-            let levels: Vec<u32> = l
-                .unwrap()
-                .split_whitespace()
-                .map(|s| s.parse::<u32>().unwrap())
-                .collect();
+            let levels = read_report(&l);
 
-            if f(&levels) {
+            if dampener.is_safe(policy, &levels) {
                 1
             } else {
                 0
@@ -87,11 +394,11 @@ fn part(f: fn(&[u32]) -> bool, input: &mut dyn Read) -> u32 {
 }
 
 fn part1(input: &mut dyn Read) -> u32 {
-    part(safe_report, input)
+    part(&Policy::PART1, input)
 }
 
 fn part2(input: &mut dyn Read) -> u32 {
-    part(safe_report_v2, input)
+    part(&Policy::PART2, input)
 }
 
 pub fn run_part1(input: &mut dyn Read) {
@@ -101,22 +408,439 @@ pub fn run_part2(input: &mut dyn Read) {
     println!("{}", part2(input));
 }
 
+pub fn run_policy(input: &mut dyn Read, args: &[String]) {
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    let policy = Policy::from_args(args);
+    if !flag("--explain") {
+        println!("{}", part(&policy, input));
+        return;
+    }
+
+    let verdicts = reader::lines(input)
+        .map(|l| explain(&policy, &read_report(&l)))
+        .enumerate()
+        .map(|(n, verdict)| (n + 1, verdict));
+
+    if flag("--json") {
+        let entries = verdicts
+            .map(|(line, verdict)| verdict_json(line, &verdict))
+            .collect::<Vec<String>>();
+        println!("[{}]", entries.join(","));
+        return;
+    }
+
+    println!("{:>5}  verdict", "line");
+    for (line, verdict) in verdicts {
+        let text = match verdict {
+            Verdict::Safe => String::from("safe"),
+            Verdict::SafeWithout(removed) => format!(
+                "safe after removing index {}",
+                removed
+                    .iter()
+                    .map(|r| r.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Verdict::Unsafe(o) => format!(
+                "unsafe at index {}: {} -> {}, {}",
+                o.pos,
+                o.prev,
+                o.level,
+                o.reason.describe()
+            ),
+            Verdict::NoDirection => String::from("unsafe, never goes up or down"),
+        };
+        println!("{line:>5}  {text}");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::xorshift::XorShift;
     use std::fs::File;
 
+    fn safe_report_v2(policy: &Policy, levels: &[u32]) -> bool {
+        Dampener::new().is_safe(policy, levels)
+    }
+
+    fn safe_report_brute(policy: &Policy, levels: &[u32]) -> bool {
+        fn tolerating(policy: &Policy, levels: &[u32], bad: usize) -> bool {
+            if safe_report(policy, levels) {
+                return true;
+            }
+            if bad == 0 {
+                return false;
+            }
+
+            (0..levels.len())
+                .map(|pos| {
+                    levels
+                        .iter()
+                        .copied()
+                        .take(pos)
+                        .chain(levels.iter().copied().skip(pos + 1))
+                        .collect::<Vec<u32>>()
+                })
+                .any(|reduced_levels| tolerating(policy, &reduced_levels, bad - 1))
+        }
+
+        tolerating(policy, levels, policy.max_bad_levels)
+    }
+
+    /// The part 2 check as it was before policies, kept as the oracle for
+    /// the presets
+    #[allow(clippy::match_like_matches_macro)]
+    mod original {
+        enum State {
+            Empty,
+            One(u32),
+            Up(u32),
+            Down(u32),
+            Unsafe,
+        }
+
+        fn safe_distance(a: u32, b: u32) -> bool {
+            const OK_DISTANCES: &[u32] = &[1, 2, 3];
+            OK_DISTANCES.contains(&a.abs_diff(b))
+        }
+
+        fn safe_step(lower: u32, higher: u32) -> bool {
+            safe_distance(lower, higher) && lower < higher
+        }
+
+        pub fn safe_report(levels: &[u32]) -> bool {
+            match levels
+                .iter()
+                .copied()
+                .fold(State::Empty, |state, level| match state {
+                    State::Empty => State::One(level),
+                    State::One(prev) => match (prev < level, safe_distance(prev, level)) {
+                        (false, true) => State::Down(level),
+                        (true, true) => State::Up(level),
+                        _ => State::Unsafe,
+                    },
+                    State::Up(prev) => {
+                        if safe_step(prev, level) {
+                            State::Up(level)
+                        } else {
+                            State::Unsafe
+                        }
+                    }
+                    State::Down(prev) => {
+                        if safe_step(level, prev) {
+                            State::Down(level)
+                        } else {
+                            State::Unsafe
+                        }
+                    }
+                    a => a,
+                }) {
+                State::Up(_) => true,
+                State::Down(_) => true,
+                _ => false,
+            }
+        }
+
+        pub fn safe_report_v2(levels: &[u32]) -> bool {
+            if safe_report(levels) {
+                return true;
+            }
+
+            (0..levels.len())
+                .map(|pos| {
+                    levels
+                        .iter()
+                        .copied()
+                        .take(pos)
+                        .chain(levels.iter().copied().skip(pos + 1))
+                        .collect::<Vec<u32>>()
+                })
+                .any(|reduced_levels| safe_report(&reduced_levels))
+        }
+    }
+
+    fn random_reports(seed: u64, count: usize) -> Vec<Vec<u32>> {
+        let mut rng = XorShift::new(seed);
+        let mut next = move || rng.next().unwrap();
+        (0..count)
+            .map(|_| {
+                let len = (next() % 10) as usize;
+                let mut level = 10 + (next() % 5) as u32;
+                (0..len)
+                    .map(|_| {
+                        level = (level + (next() % 9) as u32).saturating_sub(4);
+                        level
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
     #[test]
     fn test_safe_report() {
-        assert!(safe_report(&[1, 2, 3, 4, 5]));
-        assert!(safe_report(&[5, 4, 3, 2, 1]));
-        assert!(safe_report(&[7, 6, 4, 2, 1]));
+        assert!(safe_report(&Policy::PART1, &[1, 2, 3, 4, 5]));
+        assert!(safe_report(&Policy::PART1, &[5, 4, 3, 2, 1]));
+        assert!(safe_report(&Policy::PART1, &[7, 6, 4, 2, 1]));
     }
 
     #[test]
     fn test_safe_report_v2() {
-        assert!(safe_report(&[7, 6, 4, 2, 1]));
-        assert!(!safe_report(&[1, 2, 7, 8, 9]));
+        assert!(safe_report(&Policy::PART1, &[7, 6, 4, 2, 1]));
+        assert!(!safe_report(&Policy::PART1, &[1, 2, 7, 8, 9]));
+    }
+
+    #[test]
+    fn test_short_reports() {
+        for policy in [Policy::PART1, Policy::PART2] {
+            assert!(!safe_report_v2(&policy, &[]));
+            assert!(!safe_report_v2(&policy, &[5]));
+            assert!(safe_report_v2(&policy, &[4, 5]));
+            assert!(!safe_report_v2(&policy, &[3, 3]));
+            assert_eq!(explain(&policy, &[5]), Verdict::NoDirection);
+        }
+        assert!(!safe_report_v2(&Policy::PART2, &[1, 9]));
+        assert!(safe_report_v2(&Policy::PART2, &[1, 9, 3]));
+
+        let mut reports = "1 2 3\n\n4 5 6\n5\n1 9\n3 3\n".as_bytes();
+        assert_eq!(part1(&mut reports), 2);
+        let mut reports = "1 2 3\n\n4 5 6\n5\n1 9\n3 3\n".as_bytes();
+        assert_eq!(part2(&mut reports), 2);
+    }
+
+    #[test]
+    fn test_policy_settings() {
+        let levels = [1, 3, 3, 7, 8];
+        assert!(!safe_report(&Policy::PART1, &levels));
+
+        let relaxed = Policy {
+            max_step: 4,
+            allow_plateaus: true,
+            ..Policy::PART1
+        };
+        assert!(safe_report(&relaxed, &levels));
+        assert!(!safe_report(
+            &Policy {
+                direction: Direction::Down,
+                ..relaxed
+            },
+            &levels
+        ));
+        assert!(safe_report(
+            &Policy {
+                direction: Direction::Up,
+                ..relaxed
+            },
+            &levels
+        ));
+
+        assert!(!safe_report_v2(&Policy::PART2, &[1, 9, 2, 9, 3]));
+        assert!(safe_report_v2(
+            &Policy {
+                max_bad_levels: 2,
+                ..Policy::PART2
+            },
+            &[1, 9, 2, 9, 3]
+        ));
+    }
+
+    #[test]
+    fn test_dampener_matches_brute_force() {
+        let policies = [
+            Policy {
+                max_bad_levels: 2,
+                ..Policy::PART2
+            },
+            Policy {
+                max_bad_levels: 3,
+                allow_plateaus: true,
+                max_step: 2,
+                ..Policy::PART2
+            },
+            Policy {
+                max_bad_levels: 1,
+                direction: Direction::Down,
+                min_step: 2,
+                max_step: 4,
+                ..Policy::PART2
+            },
+        ];
+        let mut dampener = Dampener::new();
+        for seed in 1..20 {
+            for levels in random_reports(seed, 500) {
+                assert_eq!(
+                    dampener.is_safe(&Policy::PART1, &levels),
+                    original::safe_report(&levels),
+                    "{levels:?} with part 1"
+                );
+                assert_eq!(
+                    dampener.is_safe(&Policy::PART2, &levels),
+                    original::safe_report_v2(&levels),
+                    "{levels:?} with part 2"
+                );
+                for policy in &policies {
+                    assert_eq!(
+                        dampener.is_safe(policy, &levels),
+                        safe_report_brute(policy, &levels),
+                        "{levels:?} with {policy:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_removals_leave_safe_reports() {
+        let policy = Policy {
+            max_bad_levels: 2,
+            ..Policy::PART2
+        };
+        let mut dampener = Dampener::new();
+        for levels in random_reports(7, 2000) {
+            let Some(removed) = dampener.removals(&policy, &levels) else {
+                assert!(!safe_report_brute(&policy, &levels), "{levels:?}");
+                continue;
+            };
+            let kept = levels
+                .iter()
+                .enumerate()
+                .filter(|(pos, _)| !removed.contains(pos))
+                .map(|(_, &level)| level)
+                .collect::<Vec<u32>>();
+            assert!(
+                safe_report(&policy, &kept),
+                "{levels:?} without {removed:?}"
+            );
+            assert!(
+                removed.is_empty() || !safe_report(&policy, &levels),
+                "{levels:?}"
+            );
+            assert!(removed.len() <= 1 || !original::safe_report_v2(&levels));
+        }
+    }
+
+    #[test]
+    fn test_many_bad_levels() {
+        let policy = Policy::from_args(&["bad=20".to_string()]);
+        let mut levels = vec![50; 20];
+        levels.extend([1, 2]);
+        assert!(safe_report_v2(&policy, &levels));
+        levels.push(3);
+        assert!(safe_report_v2(&policy, &levels));
+        assert!(!safe_report_v2(&policy, &[50; 30]));
+        levels.push(50);
+        assert!(!safe_report_v2(&policy, &levels));
+    }
+
+    #[test]
+    fn test_explain_example() {
+        let reports = std::fs::read_to_string("input/d02-e.txt").unwrap();
+        let verdicts = reports
+            .lines()
+            .map(|l| explain(&Policy::PART2, &read_report(l)))
+            .collect::<Vec<Verdict>>();
+
+        let offence = |pos, prev, level, reason| {
+            Verdict::Unsafe(Offence {
+                pos,
+                prev,
+                level,
+                reason,
+            })
+        };
+        assert_eq!(
+            verdicts,
+            vec![
+                Verdict::Safe,
+                offence(2, 2, 7, Reason::TooLarge),
+                offence(3, 6, 2, Reason::TooLarge),
+                Verdict::SafeWithout(vec![1]),
+                Verdict::SafeWithout(vec![2]),
+                Verdict::Safe,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_reasons() {
+        let explain1 = |levels: &[u32]| match explain(&Policy::PART1, levels) {
+            Verdict::Unsafe(o) => Some((o.pos, o.reason)),
+            _ => None,
+        };
+        assert_eq!(explain1(&[1, 3, 2]), Some((2, Reason::WrongDirection)));
+        assert_eq!(explain1(&[4, 4, 5]), Some((1, Reason::Plateau)));
+        assert_eq!(explain1(&[9, 8, 4]), Some((2, Reason::TooLarge)));
+        assert_eq!(explain1(&[1, 2, 3]), None);
+
+        let down = Policy {
+            direction: Direction::Down,
+            min_step: 2,
+            ..Policy::PART1
+        };
+        assert_eq!(
+            first_offence(&down, &[1, 2]).map(|o| o.reason),
+            Some(Reason::WrongDirection)
+        );
+        assert_eq!(
+            first_offence(&down, &[3, 2]).map(|o| o.reason),
+            Some(Reason::TooSmall)
+        );
+        assert_eq!(
+            explain(
+                &Policy {
+                    max_bad_levels: 2,
+                    ..Policy::PART1
+                },
+                &[1, 9, 2, 9, 3]
+            ),
+            Verdict::SafeWithout(vec![1, 3])
+        );
+    }
+
+    #[test]
+    fn test_verdict_json() {
+        assert_eq!(
+            verdict_json(3, &explain(&Policy::PART2, &[1, 2, 7, 8, 9])),
+            r#"{"line":3,"verdict":"unsafe","index":2,"pair":[2,7],"reason":"step too large"}"#
+        );
+        assert_eq!(
+            verdict_json(4, &explain(&Policy::PART2, &[1, 3, 2, 4, 5])),
+            r#"{"line":4,"verdict":"dampened","removed":[1]}"#
+        );
+    }
+
+    #[test]
+    fn test_policy_from_args() {
+        assert_eq!(Policy::from_args(&[]), Policy::PART1);
+        assert_eq!(Policy::from_args(&["preset=p2".to_string()]), Policy::PART2);
+
+        let args = ["max=5", "plateaus=yes", "dir=down", "bad=2"].map(String::from);
+        assert_eq!(
+            Policy::from_args(&args),
+            Policy {
+                min_step: 1,
+                max_step: 5,
+                allow_plateaus: true,
+                direction: Direction::Down,
+                max_bad_levels: 2,
+            }
+        );
+
+        let args = ["bad=2", "preset=p2", "min=2"].map(String::from);
+        assert_eq!(
+            Policy::from_args(&args),
+            Policy {
+                min_step: 2,
+                max_bad_levels: 2,
+                ..Policy::PART2
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "min should not be more than max")]
+    fn test_policy_from_args_empty_range() {
+        Policy::from_args(&["min=4".to_string()]);
     }
 
     #[test]
@@ -1,179 +1,701 @@
-use std::io::{BufReader, Read};
-
-#[derive(Clone, Copy)]
-enum State {
-    Init(bool),
-    M,
-    Mu,
-    Mul,
-    OpeningParen,
-    A1(u32),
-    A2(u32),
-    A3(u32),
-    Comma(u32),
-    B1(u32, u32),
-    B2(u32, u32),
-    B3(u32, u32),
-    ClosingParen(u32, u32),
-    D(bool),
-    Do(bool),
-    DoOpeningParen(bool),
-    Don(bool),
-    Donn(bool),
-    Donnt(bool),
-    DonntOpeningParen(bool),
-}
-
-impl State {
-    fn step(&self, b: u8) -> State {
-        use State::*;
-        let c = b as char;
-        match (*self, c, c.to_digit(10)) {
-            (Init(true), 'm', _) => M,
-            (ClosingParen(_, _), 'm', _) => M,
-            (M, 'u', _) => Mu,
-            (Mu, 'l', _) => Mul,
-            (Mul, '(', _) => OpeningParen,
-            (OpeningParen, _, Some(d)) => A1(d),
-            (A1(a), _, Some(d)) => A2(10 * a + d),
-            (A1(a), ',', _) => Comma(a),
-            (A2(a), _, Some(d)) => A3(10 * a + d),
-            (A2(a), ',', _) => Comma(a),
-            (A3(a), ',', _) => Comma(a),
-            (Comma(a), _, Some(d)) => B1(a, d),
-            (B1(a, b), _, Some(d)) => B2(a, 10 * b + d),
-            (B1(a, b), ')', _) => ClosingParen(a, b),
-            (B2(a, b), _, Some(d)) => B3(a, 10 * b + d),
-            (B2(a, b), ')', _) => ClosingParen(a, b),
-            (B3(a, b), ')', _) => ClosingParen(a, b),
-            _ => Init(true),
-        }
-    }
-
-    fn step2(&self, b: u8) -> State {
-        use State::*;
-        let c = b as char;
-        match (*self, c, c.to_digit(10)) {
-            (Init(true), 'm', _) => M,
-            (Init(enabled), 'd', _) => D(enabled),
-            (Init(enabled), _, _) => Init(enabled),
-            (ClosingParen(_, _), 'm', _) => M,
-            (ClosingParen(_, _), 'd', _) => D(true),
-            (M, 'u', _) => Mu,
-            (M, 'd', _) => D(true),
-            (Mu, 'l', _) => Mul,
-            (Mu, 'd', _) => D(true),
-            (Mul, '(', _) => OpeningParen,
-            (Mul, 'd', _) => D(true),
-            (OpeningParen, _, Some(d)) => A1(d),
-            (OpeningParen, 'd', _) => D(true),
-            (A1(a), _, Some(d)) => A2(10 * a + d),
-            (A1(a), ',', _) => Comma(a),
-            (A1(_), 'd', _) => D(true),
-            (A2(a), _, Some(d)) => A3(10 * a + d),
-            (A2(a), ',', _) => Comma(a),
-            (A2(_), 'd', _) => D(true),
-            (A3(a), ',', _) => Comma(a),
-            (A3(_), 'd', _) => D(true),
-            (Comma(a), _, Some(d)) => B1(a, d),
-            (Comma(_), 'd', _) => D(true),
-            (B1(a, b), _, Some(d)) => B2(a, 10 * b + d),
-            (B1(a, b), ')', _) => ClosingParen(a, b),
-            (B1(_, _), 'd', _) => D(true),
-            (B2(a, b), _, Some(d)) => B3(a, 10 * b + d),
-            (B2(a, b), ')', _) => ClosingParen(a, b),
-            (B2(_, _), 'd', _) => D(true),
-            (B3(a, b), ')', _) => ClosingParen(a, b),
-            (B3(_, _), 'd', _) => D(true),
-            (D(true), 'm', _) => M,
-            (D(enabled), 'o', _) => Do(enabled),
-            (D(enabled), _, _) => Init(enabled),
-            (Do(true), 'm', _) => M,
-            (Do(enabled), '(', _) => DoOpeningParen(enabled),
-            (Do(enabled), 'n', _) => Don(enabled),
-            (Do(enabled), _, _) => Init(enabled),
-            (DoOpeningParen(true), 'm', _) => M,
-            (DoOpeningParen(_), ')', _) => Init(true),
-            (DoOpeningParen(enabled), _, _) => Init(enabled),
-            (Don(true), 'm', _) => M,
-            (Don(enabled), '\'', _) => Donn(enabled),
-            (Don(enabled), _, _) => Init(enabled),
-            (Donn(true), 'm', _) => M,
-            (Donn(enabled), 't', _) => Donnt(enabled),
-            (Donn(enabled), _, _) => Init(enabled),
-            (Donnt(true), 'm', _) => M,
-            (Donnt(enabled), '(', _) => DonntOpeningParen(enabled),
-            (Donnt(enabled), _, _) => Init(enabled),
-            (DonntOpeningParen(true), 'm', _) => M,
-            (DonntOpeningParen(_), ')', _) => Init(false),
-            (DonntOpeningParen(enabled), _, _) => Init(enabled),
-            _ => Init(true), // Boldly assuming that all the disabled states were handled separately
-        }
-    }
-}
-
-fn part1(input: &mut dyn Read) -> u32 {
-    BufReader::new(input)
-        .bytes()
-        .fold((State::Init(true), 0u32), |(state, acc), n| {
-            match state.step(n.unwrap()) {
-                State::ClosingParen(a, b) => (State::Init(true), acc + a * b),
-                state => (state, acc),
+use std::io::Read;
+
+/// Bytes read from the input at a time, each block is tokenized in parallel
+const BLOCK_SIZE: usize = 16 << 20;
+
+/// Start positions per chunk handed to a single rayon task
+const CHUNK_SIZE: usize = 64 << 10;
+
+struct Machine {
+    enabled: bool,
+    acc: u64,
+}
+
+impl Machine {
+    fn new() -> Machine {
+        Machine {
+            enabled: true,
+            acc: 0,
+        }
+    }
+}
+
+/// Runs an instruction, giving None if the total would overflow
+type Handler = fn(&mut Machine, &[u32]) -> Option<()>;
+
+fn mul(m: &mut Machine, args: &[u32]) -> Option<()> {
+    if m.enabled {
+        m.acc = m.acc.checked_add(args[0] as u64 * args[1] as u64)?;
+    }
+    Some(())
+}
+
+fn add(m: &mut Machine, args: &[u32]) -> Option<()> {
+    if m.enabled {
+        m.acc = m.acc.checked_add(args[0] as u64 + args[1] as u64)?;
+    }
+    Some(())
+}
+
+fn enable(m: &mut Machine, _: &[u32]) -> Option<()> {
+    m.enabled = true;
+    Some(())
+}
+
+fn disable(m: &mut Machine, _: &[u32]) -> Option<()> {
+    m.enabled = false;
+    Some(())
+}
+
+#[derive(Debug, PartialEq)]
+struct Overflow {
+    offset: usize,
+}
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "total overflows at offset {}", self.offset)
+    }
+}
+
+struct Instruction {
+    name: String,
+    arity: usize,
+    max_digits: usize,
+    handler: Handler,
+}
+
+impl Instruction {
+    fn max_len(&self) -> usize {
+        self.name.len() + 2 + self.arity * self.max_digits + self.arity.saturating_sub(1)
+    }
+
+    /// Parses a well-formed call to this instruction at the start of data
+    fn parse(&self, data: &[u8]) -> Result<Vec<u32>, Rejection> {
+        let name = self.name.as_bytes();
+        if !data.starts_with(name) || data.get(name.len()) != Some(&b'(') {
+            return Err(Rejection::NotACall);
+        }
+
+        let expect = |pos: usize, b: u8| match data.get(pos) {
+            Some(found) if *found == b => Ok(pos + 1),
+            Some(found) => Err(Rejection::Unexpected(pos, *found)),
+            None => Err(Rejection::Truncated),
+        };
+
+        let mut pos = name.len() + 1;
+        let mut args = Vec::with_capacity(self.arity);
+
+        for n in 0..self.arity {
+            if n > 0 {
+                pos = expect(pos, b',')?;
+            }
+
+            let digits = data[pos..]
+                .iter()
+                .take(self.max_digits + 1)
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if digits > self.max_digits {
+                return Err(Rejection::TooManyDigits(pos));
             }
-        })
-        .1
+            if digits == 0 {
+                return Err(match data.get(pos) {
+                    Some(found) => Rejection::Unexpected(pos, *found),
+                    None => Rejection::Truncated,
+                });
+            }
+
+            args.push(
+                data[pos..pos + digits]
+                    .iter()
+                    .fold(0, |a, d| 10 * a + (d - b'0') as u32),
+            );
+            pos += digits;
+        }
+
+        expect(pos, b')')?;
+        Ok(args)
+    }
+}
+
+/// Why the bytes at some offset were not accepted as a call. Positions
+/// are relative to the start of the instruction name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Rejection {
+    NotACall,
+    Unexpected(usize, u8),
+    TooManyDigits(usize),
+    Truncated,
 }
 
-fn part2(input: &mut dyn Read) -> u32 {
-    BufReader::new(input)
-        .bytes()
-        .fold((State::Init(true), 0u32), |(state, acc), n| {
-            match state.step2(n.unwrap()) {
-                State::ClosingParen(a, b) => (State::Init(true), acc + a * b),
-                state => (state, acc),
+impl Rejection {
+    /// Length of the rejected text worth showing, if it was a near miss
+    fn shown_len(&self, data: &[u8], max_digits: usize) -> usize {
+        match self {
+            Rejection::NotACall => 0,
+            Rejection::Unexpected(pos, _) => pos + 1,
+            Rejection::TooManyDigits(pos) => pos + max_digits + 1,
+            Rejection::Truncated => data.len(),
+        }
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Rejection::NotACall => write!(f, "not a call"),
+            Rejection::Unexpected(_, b) => write!(f, "unexpected {:?}", *b as char),
+            Rejection::TooManyDigits(_) => write!(f, "argument has too many digits"),
+            Rejection::Truncated => write!(f, "input ends inside the call"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Call {
+    offset: usize,
+    instruction: usize,
+    args: Vec<u32>,
+}
+
+enum Event {
+    Executed {
+        call: Call,
+        enabled: bool,
+        total: u64,
+    },
+    Overflowed(Call),
+    NearMiss {
+        offset: usize,
+        instruction: usize,
+        rejection: Rejection,
+    },
+}
+
+struct Interpreter {
+    instructions: Vec<Instruction>,
+    by_first_byte: Vec<Vec<usize>>,
+}
+
+impl Interpreter {
+    fn new() -> Interpreter {
+        Interpreter {
+            instructions: Vec::new(),
+            by_first_byte: vec![Vec::new(); 256],
+        }
+    }
+
+    fn register(&mut self, name: &str, arity: usize, max_digits: usize, handler: Handler) {
+        assert!(!name.is_empty(), "instruction name should not be empty");
+        assert!(
+            name.bytes()
+                .all(|b| !b.is_ascii_digit() && b != b'(' && b != b')' && b != b','),
+            "instruction name should not contain digits or call syntax"
+        );
+        assert!(
+            (1..=9).contains(&max_digits),
+            "arguments should have 1 to 9 digits"
+        );
+        // A call could otherwise start inside the name of another one
+        if let Some(other) = self
+            .instructions
+            .iter()
+            .find(|i| i.name.ends_with(name) || name.ends_with(&i.name))
+        {
+            panic!(
+                "instruction \"{name}\" would overlap with \"{}\"",
+                other.name
+            );
+        }
+
+        self.by_first_byte[name.as_bytes()[0] as usize].push(self.instructions.len());
+        self.instructions.push(Instruction {
+            name: String::from(name),
+            arity,
+            max_digits,
+            handler,
+        });
+    }
+
+    fn with_builtins(names: &[&str]) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        for name in names {
+            match *name {
+                "mul" => interpreter.register("mul", 2, 3, mul),
+                "add" => interpreter.register("add", 2, 3, add),
+                "do" => interpreter.register("do", 0, 1, enable),
+                "don't" => interpreter.register("don't", 0, 1, disable),
+                other => panic!("unknown instruction \"{other}\""),
+            }
+        }
+        interpreter
+    }
+
+    fn max_len(&self) -> usize {
+        self.instructions
+            .iter()
+            .map(|i| i.max_len())
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn call_at(&self, data: &[u8], offset: usize) -> Option<Call> {
+        self.by_first_byte[data[offset] as usize]
+            .iter()
+            .find_map(|idx| {
+                self.instructions[*idx]
+                    .parse(&data[offset..])
+                    .ok()
+                    .map(|args| Call {
+                        offset,
+                        instruction: *idx,
+                        args,
+                    })
+            })
+    }
+
+    /// Finds the calls starting before limit. The arguments are plain
+    /// digits and no name ends another, so calls never overlap and every
+    /// offset can be checked independently of the others.
+    fn tokenize(&self, data: &[u8], limit: usize) -> Vec<Call> {
+        (0..limit)
+            .filter_map(|offset| self.call_at(data, offset))
+            .collect()
+    }
+
+    fn tokenize_par(&self, data: &[u8], limit: usize, chunk_size: usize) -> Vec<Call> {
+        use rayon::prelude::*;
+
+        if limit <= chunk_size {
+            return self.tokenize(data, limit);
+        }
+
+        (0..limit.div_ceil(chunk_size))
+            .into_par_iter()
+            .flat_map_iter(|chunk| {
+                (chunk * chunk_size..limit.min((chunk + 1) * chunk_size))
+                    .filter_map(|offset| self.call_at(data, offset))
+            })
+            .collect()
+    }
+
+    fn execute(&self, machine: &mut Machine, calls: &[Call], base: usize) -> Result<(), Overflow> {
+        for call in calls {
+            (self.instructions[call.instruction].handler)(machine, &call.args).ok_or(Overflow {
+                offset: base + call.offset,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn trace(&self, data: &[u8]) -> Vec<Event> {
+        let mut machine = Machine::new();
+        let mut events = Vec::new();
+
+        for offset in 0..data.len() {
+            if let Some(call) = self.call_at(data, offset) {
+                let enabled = machine.enabled;
+                if self
+                    .execute(&mut machine, std::slice::from_ref(&call), 0)
+                    .is_err()
+                {
+                    events.push(Event::Overflowed(call));
+                    break;
+                }
+                events.push(Event::Executed {
+                    call,
+                    enabled,
+                    total: machine.acc,
+                });
+                continue;
+            }
+
+            for idx in self.by_first_byte[data[offset] as usize].iter() {
+                match self.instructions[*idx].parse(&data[offset..]) {
+                    Err(Rejection::NotACall) | Ok(_) => {}
+                    Err(rejection) => events.push(Event::NearMiss {
+                        offset,
+                        instruction: *idx,
+                        rejection,
+                    }),
+                }
+            }
+        }
+
+        events
+    }
+
+    fn describe(&self, data: &[u8], event: &Event) -> String {
+        match event {
+            Event::Executed {
+                call,
+                enabled,
+                total,
+            } => {
+                let args = call
+                    .args
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>();
+                format!(
+                    "{:>8} {}({}) {} total {}",
+                    call.offset,
+                    self.instructions[call.instruction].name,
+                    args.join(","),
+                    if *enabled { "enabled" } else { "disabled" },
+                    total
+                )
             }
-        })
-        .1
+            Event::Overflowed(call) => format!(
+                "{:>8} {} overflows the total",
+                call.offset, self.instructions[call.instruction].name
+            ),
+            Event::NearMiss {
+                offset,
+                instruction,
+                rejection,
+            } => {
+                let rest = &data[*offset..];
+                let shown = rejection
+                    .shown_len(rest, self.instructions[*instruction].max_digits)
+                    .min(rest.len());
+                format!(
+                    "{:>8} near miss {:?}: {}",
+                    offset,
+                    String::from_utf8_lossy(&rest[..shown]),
+                    rejection
+                )
+            }
+        }
+    }
+
+    fn run(&self, input: &mut dyn Read) -> Result<Machine, Overflow> {
+        self.run_blocks(input, BLOCK_SIZE)
+    }
+
+    fn run_blocks(&self, input: &mut dyn Read, block_size: usize) -> Result<Machine, Overflow> {
+        let carry = self.max_len() - 1;
+        let mut machine = Machine::new();
+        let mut base = 0;
+        let mut block = Vec::with_capacity(block_size + carry);
+
+        loop {
+            let n = input
+                .take(block_size as u64)
+                .read_to_end(&mut block)
+                .unwrap();
+
+            // Calls starting near the end of the block may continue in the
+            // next one, so those bytes are kept for the next round
+            let limit = if n == 0 {
+                block.len()
+            } else {
+                block.len().saturating_sub(carry)
+            };
+
+            let calls = self.tokenize_par(&block, limit, CHUNK_SIZE);
+            self.execute(&mut machine, &calls, base)?;
+
+            if n == 0 {
+                return Ok(machine);
+            }
+            block.drain(..limit);
+            base += limit;
+        }
+    }
+}
+
+fn part1(input: &mut dyn Read) -> Result<u64, Overflow> {
+    Ok(Interpreter::with_builtins(&["mul"]).run(input)?.acc)
+}
+
+fn part2(input: &mut dyn Read) -> Result<u64, Overflow> {
+    Ok(Interpreter::with_builtins(&["mul", "do", "don't"])
+        .run(input)?
+        .acc)
 }
 
 pub fn run_part1(input: &mut dyn Read) {
-    println!("{}", part1(input));
+    match part1(input) {
+        Ok(total) => println!("{}", total),
+        Err(e) => println!("error: {}", e),
+    }
 }
 
 pub fn run_part2(input: &mut dyn Read) {
-    println!("{}", part2(input));
+    match part2(input) {
+        Ok(total) => println!("{}", total),
+        Err(e) => println!("error: {}", e),
+    }
+}
+
+pub fn run_eval(input: &mut dyn Read, args: &[String]) {
+    let trace = args.iter().any(|a| a == "--trace");
+    let names = args
+        .iter()
+        .map(|a| a.as_str())
+        .filter(|a| *a != "--trace")
+        .collect::<Vec<&str>>();
+    let names = if names.is_empty() {
+        vec!["mul", "add", "do", "don't"]
+    } else {
+        names
+    };
+    let interpreter = Interpreter::with_builtins(&names);
+
+    if !trace {
+        match interpreter.run(input) {
+            Ok(machine) => println!("{}", machine.acc),
+            Err(e) => println!("error: {}", e),
+        }
+        return;
+    }
+
+    let mut data = Vec::new();
+    input.read_to_end(&mut data).unwrap();
+    for event in interpreter.trace(&data) {
+        println!("{}", interpreter.describe(&data, &event));
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::xorshift::XorShift;
     use std::fs::File;
 
+    fn fuzz_input(seed: u64, len: usize) -> Vec<u8> {
+        const PIECES: &[&str] = &[
+            "mul(2,3)",
+            "mul(45,678)",
+            "mul(",
+            "mul",
+            "mu",
+            "do()",
+            "don't()",
+            "don",
+            "do",
+            "(",
+            ",",
+            ")",
+            "1",
+            "23",
+            "456",
+            "7890",
+            "x",
+            " ",
+            "'",
+        ];
+        let mut rng = XorShift::new(seed);
+        let mut data = Vec::with_capacity(len + 8);
+        while data.len() < len {
+            let x = rng.next().unwrap();
+            data.extend(PIECES[(x % PIECES.len() as u64) as usize].bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_tokenize_example() {
+        let data = std::fs::read("input/d03-e2.txt").unwrap();
+        let interpreter = Interpreter::with_builtins(&["mul", "do", "don't"]);
+        let calls = interpreter.tokenize(&data, data.len());
+
+        assert_eq!(
+            calls
+                .iter()
+                .map(|c| (
+                    c.offset,
+                    interpreter.instructions[c.instruction].name.as_str(),
+                    c.args.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "mul", vec![2, 4]),
+                (20, "don't", vec![]),
+                (28, "mul", vec![5, 5]),
+                (48, "mul", vec![11, 8]),
+                (59, "do", vec![]),
+                (64, "mul", vec![8, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let interpreter = Interpreter::with_builtins(&["mul"]);
+        let mul = &interpreter.instructions[0];
+
+        assert_eq!(mul.parse(b"mul(123,4)"), Ok(vec![123, 4]));
+        assert_eq!(mul.parse(b"mul(1234,5)"), Err(Rejection::TooManyDigits(4)));
+        assert_eq!(
+            mul.parse(b"mul(1,2,3)"),
+            Err(Rejection::Unexpected(7, b','))
+        );
+        assert_eq!(mul.parse(b"mul(1)"), Err(Rejection::Unexpected(5, b')')));
+        assert_eq!(mul.parse(b"mul ( 1,2)"), Err(Rejection::NotACall));
+        assert_eq!(mul.parse(b"mul(4*"), Err(Rejection::Unexpected(5, b'*')));
+        assert_eq!(mul.parse(b"mul(1,2"), Err(Rejection::Truncated));
+    }
+
+    #[test]
+    fn test_trace() {
+        let data = b"mul(4*mul(2,3)don't()mul(1234,5)mul(1,1)do()mul(7,";
+        let interpreter = Interpreter::with_builtins(&["mul", "do", "don't"]);
+        let lines = interpreter
+            .trace(data)
+            .iter()
+            .map(|e| interpreter.describe(data, e))
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "       0 near miss \"mul(4*\": unexpected '*'",
+                "       6 mul(2,3) enabled total 6",
+                "      14 don't() enabled total 6",
+                "      21 near miss \"mul(1234\": argument has too many digits",
+                "      32 mul(1,1) disabled total 6",
+                "      40 do() disabled total 6",
+                "      44 near miss \"mul(7,\": input ends inside the call",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registered_handler() {
+        fn sub(m: &mut Machine, args: &[u32]) -> Option<()> {
+            if m.enabled {
+                m.acc -= args.iter().map(|a| *a as u64).sum::<u64>();
+            }
+            Some(())
+        }
+
+        let mut interpreter = Interpreter::with_builtins(&["add", "do", "don't"]);
+        interpreter.register("sub", 3, 2, sub);
+
+        let machine = interpreter
+            .run(&mut "add(100,5)sub(1,2,3)don't()add(9,9)sub(99,1,1)do()sub(1,1,50)".as_bytes())
+            .unwrap();
+        assert_eq!(machine.acc, 105 - 6 - 52);
+        assert!(machine.enabled);
+    }
+
+    #[test]
+    #[should_panic(expected = "instruction \"ul\" would overlap with \"mul\"")]
+    fn test_register_overlapping_name() {
+        fn nop(_: &mut Machine, _: &[u32]) -> Option<()> {
+            Some(())
+        }
+
+        let mut interpreter = Interpreter::with_builtins(&["mul"]);
+        interpreter.register("ul", 2, 3, nop);
+    }
+
+    #[test]
+    fn test_tokenize_par_matches_tokenize_fuzzed() {
+        let interpreter = Interpreter::with_builtins(&["mul", "add", "do", "don't"]);
+        for seed in 1..50 {
+            let data = fuzz_input(seed, 2000);
+            let expected = interpreter.tokenize(&data, data.len());
+            assert!(!expected.is_empty());
+            for chunk_size in [1, 2, 3, 8, 13, 64, 65, 500] {
+                assert_eq!(
+                    interpreter.tokenize_par(&data, data.len(), chunk_size),
+                    expected,
+                    "seed {seed}, chunk size {chunk_size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_small_blocks() {
+        let data = std::fs::read("input/d03-f.txt").unwrap();
+        let interpreter = Interpreter::with_builtins(&["mul", "do", "don't"]);
+        for block_size in [1, 5, 11, 12, 13, 1000] {
+            let machine = interpreter
+                .run_blocks(&mut data.as_slice(), block_size)
+                .unwrap();
+            assert_eq!(machine.acc, 88811886, "block size {block_size}");
+        }
+    }
+
+    #[test]
+    fn test_part1_large_products() {
+        let mut f = File::open("input/d03-l.txt").unwrap();
+        let result = part1(&mut f);
+        assert_eq!(result, Ok(5000 * 999 * 999 + 4000 * 998 * 997));
+        assert!(result.unwrap() > u32::MAX as u64);
+    }
+
+    #[test]
+    fn test_overflow() {
+        fn huge(m: &mut Machine, _: &[u32]) -> Option<()> {
+            m.acc = m.acc.checked_add(u64::MAX / 2)?;
+            Some(())
+        }
+
+        let mut interpreter = Interpreter::with_builtins(&["mul"]);
+        interpreter.register("huge", 0, 1, huge);
+
+        let data = "huge()huge()mul(1,1)xxhuge()";
+        assert_eq!(
+            interpreter.run(&mut data.as_bytes()).err(),
+            Some(Overflow { offset: 22 })
+        );
+        for block_size in [1, 3, 10] {
+            assert_eq!(
+                interpreter
+                    .run_blocks(&mut data.as_bytes(), block_size)
+                    .err(),
+                Some(Overflow { offset: 22 })
+            );
+        }
+
+        let events = interpreter.trace(data.as_bytes());
+        assert_eq!(
+            interpreter.describe(data.as_bytes(), events.last().unwrap()),
+            "      22 huge overflows the total"
+        );
+    }
+
+    #[test]
+    fn test_restart_inside_broken_call() {
+        // The old state machine lost these calls, as the 'm' that broke off
+        // a partial call reset it without being looked at again
+        for data in ["mulmul(2,3)", "mul(mul(2,3)", "mul(1,mul(2,3)"] {
+            assert_eq!(part1(&mut data.as_bytes()), Ok(6), "{data}");
+            assert_eq!(part2(&mut data.as_bytes()), Ok(6), "{data}");
+        }
+    }
+
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d03-e1.txt").unwrap();
         let safe_reports = part1(&mut f);
-        assert_eq!(safe_reports, 161);
+        assert_eq!(safe_reports, Ok(161));
     }
 
     #[test]
     fn test_part1_full() {
         let mut f = File::open("input/d03-f.txt").unwrap();
         let safe_reports = part1(&mut f);
-        assert_eq!(safe_reports, 166357705);
+        assert_eq!(safe_reports, Ok(166357705));
     }
 
     #[test]
     fn test_part2_example() {
         let mut f = File::open("input/d03-e2.txt").unwrap();
         let safe_reports = part2(&mut f);
-        assert_eq!(safe_reports, 48);
+        assert_eq!(safe_reports, Ok(48));
     }
 
     #[test]
     fn test_part2_full() {
         let mut f = File::open("input/d03-f.txt").unwrap();
         let safe_reports = part2(&mut f);
-        assert_eq!(safe_reports, 88811886);
+        assert_eq!(safe_reports, Ok(88811886));
     }
 }
@@ -1,31 +1,71 @@
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::iter::successors;
 
 struct WordSearch {
     width: usize,
     buf: Vec<char>,
 }
 
+#[derive(Debug, PartialEq)]
+struct RaggedRow {
+    line: usize,
+    len: usize,
+    expected: usize,
+    text: String,
+}
+
+impl std::fmt::Display for RaggedRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {} has {} characters, expected {}: {:?}",
+            self.line, self.len, self.expected, self.text
+        )
+    }
+}
+
 impl WordSearch {
     fn height(&self) -> usize {
-        self.buf.len() / self.width
+        self.buf.len().checked_div(self.width).unwrap_or(0)
     }
+
     fn load(input: &mut dyn Read) -> WordSearch {
-        let (pitch, buf) = {
-            let (pitch, buf) = BufReader::new(input).lines().map(|a| a.unwrap()).fold(
-                (None, String::new()),
-                |(pitch, mut buf), line| {
-                    buf.push_str(&line);
-
-                    (Some(pitch.unwrap_or(line.len())), buf)
-                },
-            );
-            (pitch.unwrap(), buf)
+        WordSearch::load_padded(input, None).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Loads a grid with the width measured in chars. Rows of different
+    /// lengths are an error unless a pad character is given, in which case
+    /// the short rows are filled up to the longest one.
+    fn load_padded(input: &mut dyn Read, pad: Option<char>) -> Result<WordSearch, RaggedRow> {
+        let rows = reader::lines(input)
+            .map(|a| a.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+
+        let width = match pad {
+            Some(_) => rows.iter().map(|r| r.len()).max().unwrap_or(0),
+            None => rows.first().map(|r| r.len()).unwrap_or(0),
         };
 
-        WordSearch {
-            width: pitch,
-            buf: buf.chars().collect(),
+        let mut buf = Vec::with_capacity(width * rows.len());
+        for (n, mut row) in rows.into_iter().enumerate() {
+            match pad {
+                Some(c) => row.resize(width, c),
+                None if row.len() != width => {
+                    return Err(RaggedRow {
+                        line: n + 1,
+                        len: row.len(),
+                        expected: width,
+                        text: row.into_iter().collect(),
+                    })
+                }
+                None => {}
+            }
+            buf.extend(row);
         }
+
+        Ok(WordSearch { width, buf })
     }
 }
 
@@ -40,72 +80,249 @@ const STEPS: &[(i8, i8)] = &[
     (-1, 1),
 ];
 
-fn pos_seq(step: (i8, i8), len: u8) -> Vec<(i8, i8)> {
-    let (drow, dcol) = step;
-    (0..len).map(|n| (n as i8 * drow, n as i8 * dcol)).collect()
+/// Aho-Corasick automaton for finding several words in one pass
+struct Automaton {
+    words: Vec<Vec<char>>,
+    goto: Vec<HashMap<char, usize>>,
+    fail: Vec<usize>,
+    outputs: Vec<Vec<usize>>,
 }
 
-fn part1(input: &mut dyn Read) -> u32 {
-    let ws = WordSearch::load(input);
-    let mut cnt = 0u32;
-
-    let needle = "XMAS";
-    let needle_len = needle.len() as u8;
-
-    for row in 0..ws.height() as isize {
-        for col in 0..ws.width as isize {
-            'check_pos: for step in STEPS.iter().copied() {
-                let seq = pos_seq(step, needle_len);
-                for (ch, (row_dif, col_dif)) in needle.chars().zip(seq.into_iter()) {
-                    let ch_row = row + row_dif as isize;
-                    if ch_row.is_negative() || ch_row >= ws.height() as isize {
-                        continue 'check_pos;
-                    }
+impl Automaton {
+    /// Empty words would match at every position without covering any
+    /// letters, so they are left out
+    fn new(words: &[&str]) -> Automaton {
+        let words = words
+            .iter()
+            .filter(|w| !w.is_empty())
+            .map(|w| w.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+        let mut goto = vec![HashMap::new()];
+        let mut outputs = vec![Vec::new()];
 
-                    let ch_col = col + col_dif as isize;
-                    if ch_col.is_negative() || ch_col >= ws.width as isize {
-                        continue 'check_pos;
+        for (idx, word) in words.iter().enumerate() {
+            let mut node = 0;
+            for ch in word.iter() {
+                node = match goto[node].get(ch) {
+                    Some(next) => *next,
+                    None => {
+                        goto.push(HashMap::new());
+                        outputs.push(Vec::new());
+                        let next = goto.len() - 1;
+                        goto[node].insert(*ch, next);
+                        next
                     }
+                };
+            }
+            outputs[node].push(idx);
+        }
 
-                    if ws.buf[ws.width * ch_row as usize + ch_col as usize] != ch {
-                        continue 'check_pos;
-                    }
+        let mut fail = vec![0; goto.len()];
+        let mut queue = goto[0].values().copied().collect::<VecDeque<usize>>();
+        while let Some(node) = queue.pop_front() {
+            for (ch, next) in goto[node].clone() {
+                let mut f = fail[node];
+                while f != 0 && !goto[f].contains_key(&ch) {
+                    f = fail[f];
                 }
+                fail[next] = goto[f].get(&ch).copied().unwrap_or(0);
 
-                cnt += 1;
+                let inherited = outputs[fail[next]].clone();
+                outputs[next].extend(inherited);
+                queue.push_back(next);
             }
         }
+
+        Automaton {
+            words,
+            goto,
+            fail,
+            outputs,
+        }
     }
 
-    cnt
+    fn next(&self, mut node: usize, ch: char) -> usize {
+        loop {
+            if let Some(next) = self.goto[node].get(&ch) {
+                return *next;
+            }
+            if node == 0 {
+                return 0;
+            }
+            node = self.fail[node];
+        }
+    }
 }
 
-fn part2(input: &mut dyn Read) -> u32 {
-    let ws = WordSearch::load(input);
-    let mut cnt = 0u32;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Match {
+    word: usize,
+    row: usize,
+    col: usize,
+    step: (i8, i8),
+}
+
+/// Cells of the grid lined up in the direction of step, one line at a
+/// time. Every line starts from a cell with no predecessor in the grid.
+fn lines(ws: &WordSearch, step: (i8, i8)) -> Vec<Vec<(usize, usize)>> {
+    let (drow, dcol) = step;
+    let (height, width) = (ws.height() as isize, ws.width as isize);
+    let inside = |row: isize, col: isize| row >= 0 && col >= 0 && row < height && col < width;
+
+    (0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .filter(|(row, col)| !inside(row - drow as isize, col - dcol as isize))
+        .map(|(row, col)| {
+            successors(Some((row, col)), |(r, c)| {
+                Some((r + drow as isize, c + dcol as isize))
+            })
+            .take_while(|(r, c)| inside(*r, *c))
+            .map(|(r, c)| (r as usize, c as usize))
+            .collect()
+        })
+        .collect()
+}
 
-    for row in 1..(ws.height() - 1) {
-        for col in 1..(ws.width - 1) {
-            if ws.buf[ws.width * row as usize + col as usize] != 'A' {
-                continue;
+fn find_words(ws: &WordSearch, automaton: &Automaton) -> Vec<Match> {
+    let mut matches = Vec::new();
+    // Palindromes and one letter words read the same in opposite directions,
+    // so a word is only counted once for the cells it covers
+    let mut seen = HashSet::new();
+
+    for step in STEPS.iter().copied() {
+        for line in lines(ws, step) {
+            let mut node = 0;
+            for (n, &last) in line.iter().enumerate() {
+                node = automaton.next(node, ws.buf[ws.width * last.0 + last.1]);
+                for word in automaton.outputs[node].iter() {
+                    let first = line[n + 1 - automaton.words[*word].len()];
+                    if !seen.insert((*word, first.min(last), first.max(last))) {
+                        continue;
+                    }
+                    let (row, col) = first;
+                    matches.push(Match {
+                        word: *word,
+                        row,
+                        col,
+                        step,
+                    });
+                }
             }
+        }
+    }
+
+    matches
+}
+
+fn part1(input: &mut dyn Read) -> u32 {
+    let ws = WordSearch::load(input);
+    find_words(&ws, &Automaton::new(&["XMAS"])).len() as u32
+}
+
+/// The X-MAS cross of part 2, '.' matches any letter
+const X_MAS: &str = "\
+M.S
+.A.
+M.S
+";
+
+#[derive(Clone, Debug, PartialEq)]
+struct Template {
+    width: usize,
+    cells: Vec<Option<char>>,
+}
+
+impl Template {
+    fn parse(text: &str) -> Template {
+        let rows = text
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                l.chars()
+                    .map(|c| if c == '.' { None } else { Some(c) })
+                    .collect::<Vec<Option<char>>>()
+            })
+            .collect::<Vec<Vec<Option<char>>>>();
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        assert!(width > 0, "template should not be empty");
+
+        Template {
+            width,
+            cells: rows
+                .into_iter()
+                .flat_map(|mut r| {
+                    r.resize(width, None);
+                    r
+                })
+                .collect(),
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.cells.len() / self.width
+    }
 
-            let topleft = ws.buf[ws.width * (row - 1) + col - 1];
-            let bottomleft = ws.buf[ws.width * (row + 1) + col - 1];
-            let topright = ws.buf[ws.width * (row - 1) + col + 1];
-            let bottomright = ws.buf[ws.width * (row + 1) + col + 1];
-
-            cnt += match (topleft, bottomright, bottomleft, topright) {
-                ('M', 'S', 'M', 'S') => 1,
-                ('M', 'S', 'S', 'M') => 1,
-                ('S', 'M', 'M', 'S') => 1,
-                ('S', 'M', 'S', 'M') => 1,
-                _ => 0,
-            };
+    fn rotate(&self) -> Template {
+        let height = self.height();
+        Template {
+            width: height,
+            cells: (0..self.width)
+                .flat_map(|row| {
+                    (0..height).map(move |col| self.cells[self.width * (height - 1 - col) + row])
+                })
+                .collect(),
         }
     }
 
-    cnt
+    fn reflect(&self) -> Template {
+        Template {
+            width: self.width,
+            cells: self
+                .cells
+                .chunks(self.width)
+                .flat_map(|r| r.iter().rev().copied())
+                .collect(),
+        }
+    }
+
+    /// The distinct rotations and reflections of the template
+    fn variants(&self) -> Vec<Template> {
+        let mut variants: Vec<Template> = Vec::new();
+        for start in [self.clone(), self.reflect()] {
+            for t in successors(Some(start), |t| Some(t.rotate())).take(4) {
+                if !variants.contains(&t) {
+                    variants.push(t);
+                }
+            }
+        }
+        variants
+    }
+
+    fn matches_at(&self, ws: &WordSearch, row: usize, col: usize) -> bool {
+        self.cells.iter().enumerate().all(|(n, cell)| match cell {
+            None => true,
+            Some(c) => ws.buf[ws.width * (row + n / self.width) + col + n % self.width] == *c,
+        })
+    }
+}
+
+fn count_template(ws: &WordSearch, template: &Template) -> u32 {
+    template
+        .variants()
+        .iter()
+        .filter(|t| t.height() <= ws.height() && t.width <= ws.width)
+        .map(|t| {
+            (0..=(ws.height() - t.height()))
+                .flat_map(|row| (0..=(ws.width - t.width)).map(move |col| (row, col)))
+                .filter(|(row, col)| t.matches_at(ws, *row, *col))
+                .count() as u32
+        })
+        .sum()
+}
+
+fn part2(input: &mut dyn Read) -> u32 {
+    let ws = WordSearch::load(input);
+    count_template(&ws, &Template::parse(X_MAS))
 }
 
 pub fn run_part1(input: &mut dyn Read) {
@@ -116,6 +333,136 @@ pub fn run_part2(input: &mut dyn Read) {
     println!("{}", part2(input));
 }
 
+pub fn run_template(input: &mut dyn Read, args: &[String]) {
+    let template = match args.first() {
+        Some(path) => Template::parse(&std::fs::read_to_string(path).unwrap()),
+        None => Template::parse(X_MAS),
+    };
+
+    println!("{}", count_template(&WordSearch::load(input), &template));
+}
+
+fn match_cells(ws: &WordSearch, automaton: &Automaton, matches: &[Match]) -> HashSet<usize> {
+    matches
+        .iter()
+        .flat_map(|m| {
+            let (drow, dcol) = m.step;
+            (0..automaton.words[m.word].len() as isize).map(move |n| {
+                let row = m.row as isize + n * drow as isize;
+                let col = m.col as isize + n * dcol as isize;
+                ws.width * row as usize + col as usize
+            })
+        })
+        .collect()
+}
+
+/// The grid with the letters outside of matches replaced by '.', or
+/// dimmed instead when colour is requested
+fn highlight(ws: &WordSearch, cells: &HashSet<usize>, colour: bool) -> String {
+    if ws.width == 0 {
+        return String::new();
+    }
+
+    ws.buf
+        .chunks(ws.width)
+        .enumerate()
+        .map(|(row, letters)| {
+            letters
+                .iter()
+                .enumerate()
+                .map(|(col, ch)| {
+                    let kept = cells.contains(&(ws.width * row + col));
+                    match (kept, colour) {
+                        (true, false) => ch.to_string(),
+                        (false, false) => String::from("."),
+                        (true, true) => format!("\x1b[1;31m{ch}\x1b[0m"),
+                        (false, true) => format!("\x1b[2m{ch}\x1b[0m"),
+                    }
+                })
+                .chain([String::from("\n")])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn json_string(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '"' => String::from("\\\""),
+            '\\' => String::from("\\\\"),
+            c if c.is_control() => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn matches_json(automaton: &Automaton, matches: &[Match]) -> String {
+    let entries = matches
+        .iter()
+        .map(|m| {
+            format!(
+                "{{\"word\":\"{}\",\"row\":{},\"col\":{},\"direction\":[{},{}]}}",
+                json_string(&automaton.words[m.word].iter().collect::<String>()),
+                m.row,
+                m.col,
+                m.step.0,
+                m.step.1
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("[{}]", entries.join(","))
+}
+
+pub fn run_words(input: &mut dyn Read, args: &[String]) {
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    let pad = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--pad="))
+        .map(|p| p.chars().next().unwrap_or('.'));
+    let words = args
+        .iter()
+        .map(|a| a.as_str())
+        .filter(|a| !a.starts_with("--"))
+        .collect::<Vec<&str>>();
+    let words = if words.is_empty() {
+        vec!["XMAS"]
+    } else {
+        words
+    };
+
+    let ws = match WordSearch::load_padded(input, pad) {
+        Ok(ws) => ws,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        }
+    };
+    let automaton = Automaton::new(&words);
+    let matches = find_words(&ws, &automaton);
+
+    if flag("--json") {
+        println!("{}", matches_json(&automaton, &matches));
+        return;
+    }
+
+    if flag("--highlight") || flag("--colour") {
+        let cells = match_cells(&ws, &automaton, &matches);
+        print!("{}", highlight(&ws, &cells, flag("--colour")));
+        return;
+    }
+
+    for (idx, word) in automaton.words.iter().enumerate() {
+        let found = matches
+            .iter()
+            .filter(|m| m.word == idx)
+            .collect::<Vec<&Match>>();
+        println!("{}: {}", word.iter().collect::<String>(), found.len());
+        for m in found {
+            println!("    ({}, {}) step {:?}", m.row, m.col, m.step);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,6 +476,177 @@ mod tests {
         assert_eq!(ws.height(), 10);
     }
 
+    #[test]
+    fn test_load_ragged() {
+        let grid = "XMAS\nXMA\nXMAS\n";
+        assert_eq!(
+            WordSearch::load_padded(&mut grid.as_bytes(), None).err(),
+            Some(RaggedRow {
+                line: 2,
+                len: 3,
+                expected: 4,
+                text: String::from("XMA"),
+            })
+        );
+
+        let grid = "XMA\nXMAS\n";
+        let ws = WordSearch::load_padded(&mut grid.as_bytes(), Some('.')).unwrap();
+        assert_eq!(ws.width, 4);
+        assert_eq!(ws.height(), 2);
+        assert_eq!(ws.buf[3], '.');
+    }
+
+    #[test]
+    fn test_load_unicode() {
+        let grid = "ÄXMAS\nÖÜßé.\n";
+        let ws = WordSearch::load_padded(&mut grid.as_bytes(), None).unwrap();
+        assert_eq!(ws.width, 5);
+        assert_eq!(ws.height(), 2);
+        assert_eq!(ws.buf[5], 'Ö');
+        assert_eq!(find_words(&ws, &Automaton::new(&["XMAS", "ßé"])).len(), 2);
+    }
+
+    #[test]
+    fn test_automaton_overlapping_words() {
+        let automaton = Automaton::new(&["HE", "SHE", "HIS", "HERS"]);
+        let found = "USHERS"
+            .chars()
+            .scan(0, |node, ch| {
+                *node = automaton.next(*node, ch);
+                Some(automaton.outputs[*node].clone())
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        assert_eq!(
+            found[3].iter().copied().collect::<HashSet<usize>>(),
+            HashSet::from([0, 1])
+        );
+        assert_eq!(found[5], vec![3]);
+        assert_eq!(found.iter().map(|o| o.len()).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_automaton_empty_word() {
+        let mut f = File::open("input/d04-e.txt").unwrap();
+        let ws = WordSearch::load(&mut f);
+        let automaton = Automaton::new(&["", "XMAS", ""]);
+        assert_eq!(automaton.words, vec![vec!['X', 'M', 'A', 'S']]);
+        assert_eq!(find_words(&ws, &automaton).len(), 18);
+        assert!(find_words(&ws, &Automaton::new(&[""])).is_empty());
+    }
+
+    #[test]
+    fn test_find_words_example() {
+        let mut f = File::open("input/d04-e.txt").unwrap();
+        let ws = WordSearch::load(&mut f);
+        let matches = find_words(&ws, &Automaton::new(&["XMAS", "SAMX", "MAS", "XMASX"]));
+
+        let count = |word| matches.iter().filter(|m| m.word == word).count();
+        assert_eq!(count(0), 18);
+        assert_eq!(count(1), 18);
+        assert_eq!(count(3), 5);
+
+        assert!(matches.contains(&Match {
+            word: 0,
+            row: 0,
+            col: 5,
+            step: (0, 1)
+        }));
+        assert!(matches.contains(&Match {
+            word: 0,
+            row: 9,
+            col: 5,
+            step: (-1, -1)
+        }));
+    }
+
+    #[test]
+    fn test_find_words_reversible() {
+        let ws = WordSearch::load(&mut "AB\nCD\n".as_bytes());
+        assert_eq!(find_words(&ws, &Automaton::new(&["A"])).len(), 1);
+        assert_eq!(find_words(&ws, &Automaton::new(&["A", "D"])).len(), 2);
+
+        let ws = WordSearch::load(&mut "ABA\n".as_bytes());
+        let matches = find_words(&ws, &Automaton::new(&["ABA", "AB", "BA"]));
+        assert_eq!(matches.iter().filter(|m| m.word == 0).count(), 1);
+        assert_eq!(matches.iter().filter(|m| m.word == 1).count(), 2);
+        assert_eq!(matches.iter().filter(|m| m.word == 2).count(), 2);
+    }
+
+    #[test]
+    fn test_template_variants() {
+        let x_mas = Template::parse(X_MAS);
+        assert_eq!(x_mas.variants().len(), 4);
+
+        let plus = Template::parse(".M.\nMAS\n.S.\n");
+        assert_eq!(plus.variants().len(), 4);
+
+        let ell = Template::parse("XM\nA\n");
+        assert_eq!(ell.width, 2);
+        assert_eq!(ell.cells, vec![Some('X'), Some('M'), Some('A'), None]);
+        assert_eq!(ell.variants().len(), 8);
+
+        let line = Template::parse("XMAS");
+        assert_eq!(line.rotate().width, 1);
+        assert_eq!(line.variants().len(), 4);
+    }
+
+    #[test]
+    fn test_template_as_words() {
+        let mut f = File::open("input/d04-f.txt").unwrap();
+        let ws = WordSearch::load(&mut f);
+        let straight = find_words(&ws, &Automaton::new(&["XMAS"]))
+            .into_iter()
+            .filter(|m| m.step.0 == 0 || m.step.1 == 0)
+            .count() as u32;
+        assert_eq!(count_template(&ws, &Template::parse("XMAS")), straight);
+
+        let diagonal = Template::parse("X...\n.M..\n..A.\n...S\n");
+        assert_eq!(count_template(&ws, &diagonal) + straight, 2434);
+    }
+
+    #[test]
+    fn test_highlight_example() {
+        let mut f = File::open("input/d04-e.txt").unwrap();
+        let ws = WordSearch::load(&mut f);
+        let automaton = Automaton::new(&["XMAS"]);
+        let matches = find_words(&ws, &automaton);
+        let cells = match_cells(&ws, &automaton, &matches);
+
+        let expected = "\
+....XXMAS.
+.SAMXMS...
+...S..A...
+..A.A.MS.X
+XMASAMX.MM
+X.....XA.A
+S.S.S.S.SS
+.A.A.A.A.A
+..M.M.M.MM
+.X.X.XMASX
+";
+        assert_eq!(highlight(&ws, &cells, false), expected);
+        assert!(highlight(&ws, &cells, true).starts_with("\x1b[2mM\x1b[0m"));
+
+        let empty = WordSearch::load(&mut "".as_bytes());
+        assert_eq!(highlight(&empty, &HashSet::new(), false), "");
+    }
+
+    #[test]
+    fn test_matches_json() {
+        let automaton = Automaton::new(&["XM\"AS"]);
+        let matches = [Match {
+            word: 0,
+            row: 3,
+            col: 9,
+            step: (1, -1),
+        }];
+        assert_eq!(
+            matches_json(&automaton, &matches),
+            "[{\"word\":\"XM\\\"AS\",\"row\":3,\"col\":9,\"direction\":[1,-1]}]"
+        );
+    }
+
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d04-e.txt").unwrap();
@@ -1,92 +1,517 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::collections::HashSet;
+use std::io::Read;
+use std::iter::Peekable;
+
+/// Page numbers below this are kept in a bit matrix, larger ones in a set
+const DENSE_LIMIT: u32 = 1024;
+
+/// The ordering rules, each X|Y stored once as an edge from X to Y
+enum Rules {
+    Dense {
+        size: usize,
+        bits: Vec<u64>,
+        len: usize,
+    },
+    Sparse(HashSet<(u32, u32)>),
+}
+
+impl Rules {
+    fn from_pairs(pairs: &[(u32, u32)]) -> Rules {
+        let max_page = pairs.iter().map(|(a, b)| *a.max(b)).max().unwrap_or(0);
+
+        if max_page >= DENSE_LIMIT {
+            return Rules::Sparse(pairs.iter().copied().collect());
+        }
+
+        let size = max_page as usize + 1;
+        let mut bits = vec![0u64; (size * size).div_ceil(64)];
+        let mut len = 0;
+        for (before, after) in pairs.iter() {
+            let bit = *before as usize * size + *after as usize;
+            if bits[bit / 64] & (1 << (bit % 64)) == 0 {
+                bits[bit / 64] |= 1 << (bit % 64);
+                len += 1;
+            }
+        }
+
+        Rules::Dense { size, bits, len }
+    }
+
+    fn before(&self, a: u32, b: u32) -> bool {
+        match self {
+            Rules::Dense { size, bits, .. } => {
+                let (a, b) = (a as usize, b as usize);
+                if a >= *size || b >= *size {
+                    return false;
+                }
+                let bit = a * size + b;
+                bits[bit / 64] & (1 << (bit % 64)) != 0
+            }
+            Rules::Sparse(pairs) => pairs.contains(&(a, b)),
+        }
+    }
+
+    fn pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = match self {
+            Rules::Dense { size, bits, .. } => (0..size * size)
+                .filter(|bit| bits[bit / 64] & (1 << (bit % 64)) != 0)
+                .map(|bit| ((bit / size) as u32, (bit % size) as u32))
+                .collect(),
+            Rules::Sparse(pairs) => pairs.iter().copied().collect::<Vec<(u32, u32)>>(),
+        };
+        pairs.sort();
+        pairs
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Rules::Dense { len, .. } => *len,
+            Rules::Sparse(pairs) => pairs.len(),
+        }
+    }
+}
 
 struct Print {
-    ordering_rules: HashMap<(u8, u8), Ordering>,
-    updates: Vec<Vec<u8>>,
+    ordering_rules: Rules,
+    updates: Vec<Vec<u32>>,
 }
 
-impl Print {
-    fn buf(&self) -> Vec<u8> {
-        let sz = self.updates.iter().map(|v| v.len()).max().unwrap();
-        let mut buf = Vec::with_capacity(sz);
-        buf.resize(sz, 0);
-        buf
+enum Line {
+    Blank,
+    Rule(u32, u32),
+    Update(Vec<u32>),
+}
+
+fn parse_line(l: &str) -> Line {
+    let l = l.trim();
+    if l.is_empty() {
+        Line::Blank
+    } else if let Some((before, after)) = l.split_once('|') {
+        Line::Rule(
+            before.trim().parse::<u32>().unwrap(),
+            after.trim().parse::<u32>().unwrap(),
+        )
+    } else {
+        Line::Update(
+            l.split(',')
+                .map(|p| p.trim().parse::<u32>().unwrap())
+                .collect(),
+        )
     }
+}
 
+impl Print {
+    /// Rules and updates are told apart by their separators, so they can
+    /// come in any order and with any number of blank lines in between
     fn load(input: &mut dyn Read) -> Print {
-        let (rules, updates, _) = BufReader::new(input).lines().map(|l| l.unwrap()).fold(
-            (HashMap::new(), Vec::new(), true),
-            |(mut rules, mut updates, collect_rules), l| {
-                if l.is_empty() {
-                    (rules, updates, false)
-                } else if collect_rules {
-                    let (before, after) = {
-                        let mut pieces = l.split('|').map(|a| a.parse::<u8>().unwrap());
-                        let before = pieces.next().unwrap();
-                        let after = pieces.next().unwrap();
-                        (before, after)
-                    };
-                    rules.insert((before, after), Ordering::Less);
-                    rules.insert((after, before), Ordering::Greater);
-                    (rules, updates, true)
-                } else {
-                    updates.push(l.split(',').map(|p| p.parse::<u8>().unwrap()).collect());
-                    (rules, updates, false)
+        let (rules, updates) =
+            reader::lines(input).fold((Vec::new(), Vec::new()), |(mut rules, mut updates), l| {
+                match parse_line(&l) {
+                    Line::Blank => {}
+                    Line::Rule(before, after) => rules.push((before, after)),
+                    Line::Update(pages) => updates.push(pages),
                 }
-            },
-        );
+                (rules, updates)
+            });
 
         Print {
-            ordering_rules: rules,
-            updates: updates,
+            ordering_rules: Rules::from_pairs(&rules),
+            updates,
         }
     }
 }
 
-fn sort_by_rules(rules: &HashMap<(u8, u8), Ordering>, buf: &mut [u8]) {
-    buf.sort_by(|a, b| {
-        if a == b {
-            return Ordering::Equal;
+#[derive(Debug, PartialEq)]
+enum Ordered {
+    /// The rules allow exactly one order for the pages
+    Unique(Vec<u32>),
+    /// Some pages are not ordered relative to each other. The order given
+    /// keeps those pages as they were in the update.
+    Ambiguous(Vec<u32>),
+    /// The rules between these pages form a cycle
+    Cycle(Vec<u32>),
+}
+
+/// Topological sort of the rule graph restricted to the pages of an update
+fn topo_sort(rules: &Rules, update: &[u32]) -> Ordered {
+    let n = update.len();
+    let edges = |from: usize| (0..n).filter(move |to| rules.before(update[from], update[*to]));
+
+    let mut in_degree = vec![0; n];
+    for from in 0..n {
+        for to in edges(from) {
+            in_degree[to] += 1;
+        }
+    }
+
+    let mut placed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut unique = true;
+
+    while order.len() < n {
+        let mut ready = (0..n).filter(|i| !placed[*i] && in_degree[*i] == 0);
+        let next = match ready.next() {
+            Some(i) => i,
+            None => break,
+        };
+        if ready.next().is_some() {
+            unique = false;
         }
 
-        match rules.get(&(*a, *b)) {
-            Some(o) => *o,
-            None => a.cmp(b),
+        placed[next] = true;
+        order.push(update[next]);
+        for to in edges(next) {
+            in_degree[to] -= 1;
         }
+    }
+
+    if order.len() < n {
+        // Every page left has a predecessor that is also left, so walking
+        // backwards from any of them has to come around to a repeat
+        let mut walk = vec![(0..n).find(|i| !placed[*i]).unwrap()];
+        loop {
+            let current = *walk.last().unwrap();
+            let prev = (0..n)
+                .find(|from| !placed[*from] && rules.before(update[*from], update[current]))
+                .unwrap();
+            if let Some(start) = walk.iter().position(|i| *i == prev) {
+                return Ordered::Cycle(walk[start..].iter().rev().map(|i| update[*i]).collect());
+            }
+            walk.push(prev);
+        }
+    }
+
+    if unique {
+        Ordered::Unique(order)
+    } else {
+        Ordered::Ambiguous(order)
+    }
+}
+
+fn is_correct_update(rules: &Rules, update: &[u32]) -> bool {
+    (0..update.len()).all(|i| {
+        update[i + 1..]
+            .iter()
+            .all(|later| !rules.before(*later, update[i]))
     })
 }
 
-fn is_correct_update(rules: &HashMap<(u8, u8), Ordering>, update: &[u8], buf: &mut [u8]) -> bool {
-    buf.copy_from_slice(update);
-    sort_by_rules(rules, buf);
-    buf == update
+/// A rule before|after broken by the update listing after first
+#[derive(Debug, PartialEq)]
+struct Violation {
+    before: u32,
+    after: u32,
+    before_pos: usize,
+    after_pos: usize,
+}
+
+fn violations(rules: &Rules, update: &[u32]) -> Vec<Violation> {
+    (0..update.len())
+        .flat_map(|after_pos| {
+            (after_pos + 1..update.len())
+                .filter(move |before_pos| rules.before(update[*before_pos], update[after_pos]))
+                .map(move |before_pos| Violation {
+                    before: update[before_pos],
+                    after: update[after_pos],
+                    before_pos,
+                    after_pos,
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct Move {
+    page: u32,
+    from: usize,
+    to: usize,
+}
+
+/// The fewest pages to move to turn update into order. Pages forming the
+/// longest run already in the right relative order stay where they are.
+fn fixing_moves(update: &[u32], order: &[u32]) -> Vec<Move> {
+    let rank = |page: u32| order.iter().position(|p| *p == page).unwrap();
+    let ranks = update.iter().map(|p| rank(*p)).collect::<Vec<usize>>();
+
+    // Longest increasing subsequence of the ranks, lengths and back links
+    let mut best = vec![(1usize, None); ranks.len()];
+    for i in 0..ranks.len() {
+        for j in 0..i {
+            if ranks[j] < ranks[i] && best[j].0 + 1 > best[i].0 {
+                best[i] = (best[j].0 + 1, Some(j));
+            }
+        }
+    }
+
+    let mut keep = vec![false; ranks.len()];
+    let mut at = (0..ranks.len()).max_by_key(|i| (best[*i].0, std::cmp::Reverse(*i)));
+    while let Some(i) = at {
+        keep[i] = true;
+        at = best[i].1;
+    }
+
+    (0..update.len())
+        .filter(|i| !keep[*i])
+        .map(|i| Move {
+            page: update[i],
+            from: i,
+            to: ranks[i],
+        })
+        .collect()
+}
+
+/// Strongly connected components of the rule graph, Tarjan's algorithm
+fn components(pages: &[u32], edges: &[(u32, u32)]) -> Vec<Vec<u32>> {
+    struct Tarjan<'a> {
+        pages: &'a [u32],
+        edges: &'a [(u32, u32)],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        out: Vec<Vec<u32>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, v: usize) {
+            self.index[v] = Some(self.next);
+            self.low[v] = self.next;
+            self.next += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+
+            let successors = self
+                .edges
+                .iter()
+                .filter(|(a, _)| *a == self.pages[v])
+                .map(|(_, b)| self.pages.iter().position(|p| p == b).unwrap())
+                .collect::<Vec<usize>>();
+            for w in successors {
+                match self.index[w] {
+                    None => {
+                        self.visit(w);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    }
+                    Some(idx) if self.on_stack[w] => self.low[v] = self.low[v].min(idx),
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[v]) == self.index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(self.pages[w]);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                self.out.push(component);
+            }
+        }
+    }
+
+    let mut t = Tarjan {
+        pages,
+        edges,
+        index: vec![None; pages.len()],
+        low: vec![0; pages.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; pages.len()],
+        next: 0,
+        out: Vec::new(),
+    };
+    for v in 0..pages.len() {
+        if t.index[v].is_none() {
+            t.visit(v);
+        }
+    }
+    t.out
 }
 
-fn part1(input: &mut dyn Read) -> u32 {
+/// The rules as a Graphviz digraph. With an update given, only its pages
+/// are included and the rules it breaks are drawn in red. Pages in the
+/// same cycle are grouped into a cluster.
+fn dot(rules: &Rules, update: Option<&[u32]>) -> String {
+    let edges = rules
+        .pairs()
+        .into_iter()
+        .filter(|(a, b)| update.is_none_or(|u| u.contains(a) && u.contains(b)))
+        .collect::<Vec<(u32, u32)>>();
+    let mut pages = match update {
+        Some(u) => u.to_vec(),
+        None => edges.iter().flat_map(|(a, b)| [*a, *b]).collect(),
+    };
+    pages.sort();
+    pages.dedup();
+
+    let broken = update
+        .map(|u| {
+            violations(rules, u)
+                .into_iter()
+                .map(|v| (v.before, v.after))
+                .collect::<HashSet<(u32, u32)>>()
+        })
+        .unwrap_or_default();
+
+    let mut out = String::from("digraph rules {\n");
+    for (n, component) in components(&pages, &edges)
+        .into_iter()
+        .filter(|c| c.len() > 1)
+        .enumerate()
+    {
+        out.push_str(&format!(
+            "    subgraph cluster_{n} {{\n        label=\"cycle\";\n        color=red;\n"
+        ));
+        for page in component {
+            out.push_str(&format!("        p{page};\n"));
+        }
+        out.push_str("    }\n");
+    }
+    for page in pages.iter() {
+        out.push_str(&format!("    p{page} [label=\"{page}\"];\n"));
+    }
+    for (a, b) in edges.iter() {
+        if broken.contains(&(*a, *b)) {
+            out.push_str(&format!("    p{a} -> p{b} [color=red];\n"));
+        } else {
+            out.push_str(&format!("    p{a} -> p{b};\n"));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Status {
+    Correct,
+    Fixed(Vec<u32>),
+    Unfixable(Cycle),
+}
+
+/// Outcome for one update, with the part 1 and part 2 sums so far
+#[derive(Debug, PartialEq)]
+struct Verdict {
+    update: Vec<u32>,
+    status: Status,
+    correct_sum: u64,
+    fixed_sum: u64,
+}
+
+/// What the stream reports for one line after the rules
+#[derive(Debug, PartialEq)]
+enum Event {
+    Update(Verdict),
+    /// A rule after the first update, which is ignored because the updates
+    /// before it have already been checked
+    LateRule(u32, u32),
+}
+
+/// Checks updates one line at a time after the rules have been read
+struct Verdicts<'a> {
+    lines: Peekable<reader::Lines<'a>>,
+    rules: Rules,
+    correct_sum: u64,
+    fixed_sum: u64,
+}
+
+impl Verdicts<'_> {
+    /// Reads the rules, which have to come before the first update
+    fn new(input: &mut dyn Read) -> Verdicts<'_> {
+        let mut lines = reader::lines(input).peekable();
+        let mut pairs = Vec::new();
+
+        while let Some(line) = lines.peek() {
+            match parse_line(line) {
+                Line::Blank => {}
+                Line::Rule(before, after) => pairs.push((before, after)),
+                Line::Update(_) => break,
+            }
+            lines.next();
+        }
+
+        Verdicts {
+            lines,
+            rules: Rules::from_pairs(&pairs),
+            correct_sum: 0,
+            fixed_sum: 0,
+        }
+    }
+}
+
+impl Iterator for Verdicts<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let update = loop {
+            match parse_line(&self.lines.next()?) {
+                Line::Blank => continue,
+                Line::Update(pages) => break pages,
+                Line::Rule(before, after) => return Some(Event::LateRule(before, after)),
+            }
+        };
+
+        let status = if is_correct_update(&self.rules, &update) {
+            self.correct_sum += update[update.len() / 2] as u64;
+            Status::Correct
+        } else {
+            match topo_sort(&self.rules, &update) {
+                Ordered::Unique(o) | Ordered::Ambiguous(o) => {
+                    self.fixed_sum += o[o.len() / 2] as u64;
+                    Status::Fixed(o)
+                }
+                Ordered::Cycle(pages) => Status::Unfixable(Cycle(pages)),
+            }
+        };
+
+        Some(Event::Update(Verdict {
+            update,
+            status,
+            correct_sum: self.correct_sum,
+            fixed_sum: self.fixed_sum,
+        }))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Cycle(Vec<u32>);
+
+impl std::fmt::Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let pages = self
+            .0
+            .iter()
+            .chain(self.0.first())
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "rules form a cycle: {}", pages.join(" -> "))
+    }
+}
+
+fn part1(input: &mut dyn Read) -> u64 {
     let print = Print::load(input);
-    let mut buf = print.buf();
 
     print
         .updates
         .iter()
-        .filter(|u| is_correct_update(&print.ordering_rules, u.as_ref(), &mut buf[0..u.len()]))
-        .map(|u| u[u.len() / 2] as u32)
+        .filter(|u| is_correct_update(&print.ordering_rules, u))
+        .map(|u| u[u.len() / 2] as u64)
         .sum()
 }
 
-fn part2(input: &mut dyn Read) -> u32 {
+fn part2(input: &mut dyn Read) -> Result<u64, Cycle> {
     let print = Print::load(input);
-    let mut buf = print.buf();
 
     print
         .updates
-        .into_iter()
-        .filter(|u| !is_correct_update(&print.ordering_rules, u.as_ref(), &mut buf[0..u.len()]))
-        .map(|mut u| {
-            sort_by_rules(&print.ordering_rules, &mut u);
-            u[u.len() / 2] as u32
+        .iter()
+        .filter(|u| !is_correct_update(&print.ordering_rules, u))
+        .map(|u| match topo_sort(&print.ordering_rules, u) {
+            Ordered::Unique(o) | Ordered::Ambiguous(o) => Ok(o[o.len() / 2] as u64),
+            Ordered::Cycle(pages) => Err(Cycle(pages)),
         })
         .sum()
 }
@@ -96,7 +521,131 @@ pub fn run_part1(input: &mut dyn Read) {
 }
 
 pub fn run_part2(input: &mut dyn Read) {
-    println!("{}", part2(input));
+    match part2(input) {
+        Ok(sum) => println!("{}", sum),
+        Err(e) => println!("error: {}", e),
+    }
+}
+
+pub fn run_explain(input: &mut dyn Read) {
+    let print = Print::load(input);
+
+    for (n, update) in print.updates.iter().enumerate() {
+        let broken = violations(&print.ordering_rules, update);
+        if broken.is_empty() {
+            continue;
+        }
+
+        let pages = update
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        println!("update {}: {}", n + 1, pages.join(","));
+        for v in broken.iter() {
+            println!(
+                "    violates {}|{}: {} at {} comes after {} at {}",
+                v.before, v.after, v.before, v.before_pos, v.after, v.after_pos
+            );
+        }
+
+        match topo_sort(&print.ordering_rules, update) {
+            Ordered::Unique(order) | Ordered::Ambiguous(order) => {
+                for m in fixing_moves(update, &order) {
+                    println!("    move {} from {} to {}", m.page, m.from, m.to);
+                }
+            }
+            Ordered::Cycle(pages) => println!("    cannot fix, {}", Cycle(pages)),
+        }
+    }
+}
+
+pub fn run_stream(input: &mut dyn Read) {
+    for event in Verdicts::new(input) {
+        let verdict = match event {
+            Event::Update(verdict) => verdict,
+            Event::LateRule(before, after) => {
+                println!("rule {before}|{after} after the first update: ignored");
+                continue;
+            }
+        };
+        let pages = verdict
+            .update
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        let status = match verdict.status {
+            Status::Correct => String::from("correct"),
+            Status::Fixed(order) => {
+                let order = order.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+                format!("fixed to {}", order.join(","))
+            }
+            Status::Unfixable(cycle) => cycle.to_string(),
+        };
+        println!(
+            "{}: {} (sums {} {})",
+            pages.join(","),
+            status,
+            verdict.correct_sum,
+            verdict.fixed_sum
+        );
+    }
+}
+
+pub fn run_dot(input: &mut dyn Read, args: &[String]) {
+    let print = Print::load(input);
+    let update = match args.first() {
+        None => None,
+        Some(a) => {
+            let n = a.parse::<usize>().expect("update should be a number");
+            match n.checked_sub(1).and_then(|i| print.updates.get(i)) {
+                Some(update) => Some(update.as_slice()),
+                None => {
+                    println!(
+                        "error: update {} is out of range, there are {} updates",
+                        n,
+                        print.updates.len()
+                    );
+                    return;
+                }
+            }
+        }
+    };
+
+    print!("{}", dot(&print.ordering_rules, update));
+}
+
+pub fn run_check(input: &mut dyn Read) {
+    let print = Print::load(input);
+    println!(
+        "{} rules, {} updates",
+        print.ordering_rules.len(),
+        print.updates.len()
+    );
+
+    for update in print.updates.iter() {
+        let pages = update
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        let verdict = match topo_sort(&print.ordering_rules, update) {
+            Ordered::Cycle(pages) => Cycle(pages).to_string(),
+            ordered => {
+                let (kind, order) = match ordered {
+                    Ordered::Unique(o) => ("unique", o),
+                    Ordered::Ambiguous(o) => ("ambiguous", o),
+                    Ordered::Cycle(_) => unreachable!(),
+                };
+                let status = if is_correct_update(&print.ordering_rules, update) {
+                    "correct"
+                } else {
+                    "incorrect"
+                };
+                let order = order.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+                format!("{}, {} order {}", status, kind, order.join(","))
+            }
+        };
+        println!("{}: {}", pages.join(","), verdict);
+    }
 }
 
 #[cfg(test)]
@@ -108,10 +657,237 @@ mod tests {
     fn test_load() {
         let mut f = File::open("input/d05-e.txt").unwrap();
         let p = Print::load(&mut f);
-        assert_eq!(p.ordering_rules.len(), 42);
+        assert_eq!(p.ordering_rules.len(), 21);
         assert_eq!(p.updates.len(), 6);
     }
 
+    fn rules(pairs: &[(u32, u32)]) -> Rules {
+        Rules::from_pairs(pairs)
+    }
+
+    #[test]
+    fn test_rules_dense_and_sparse() {
+        let dense = Rules::from_pairs(&[(1, 2), (2, 3), (1, 2), (300, 7)]);
+        assert!(matches!(dense, Rules::Dense { .. }));
+        let sparse = Rules::from_pairs(&[(1, 2), (2, 3), (1, 2), (300, 70000)]);
+        assert!(matches!(sparse, Rules::Sparse(_)));
+
+        for r in [dense, sparse] {
+            assert_eq!(r.len(), 3);
+            assert!(r.before(1, 2));
+            assert!(!r.before(2, 1));
+            assert!(!r.before(1, 3));
+            assert!(!r.before(5000, 1));
+        }
+    }
+
+    #[test]
+    fn test_components() {
+        let edges = [(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4), (6, 1)];
+        let mut found = components(&[1, 2, 3, 4, 5, 6], &edges);
+        found.sort();
+        assert_eq!(found, vec![vec![1, 2, 3], vec![4, 5], vec![6]]);
+    }
+
+    #[test]
+    fn test_dot() {
+        let mut f = File::open("input/d05-e.txt").unwrap();
+        let p = Print::load(&mut f);
+
+        let full = dot(&p.ordering_rules, None);
+        assert_eq!(full.matches(" -> ").count(), 21);
+        assert!(!full.contains("cluster"));
+
+        let update = dot(&p.ordering_rules, Some(&[75, 97, 47, 61, 53]));
+        assert_eq!(update.matches(" -> ").count(), 10);
+        assert!(update.contains("    p97 -> p75 [color=red];\n"));
+        assert_eq!(update.matches("color=red").count(), 1);
+
+        let cyclic = Rules::from_pairs(&[(1, 2), (2, 3), (3, 1), (3, 4)]);
+        let graph = dot(&cyclic, None);
+        assert!(graph.contains("subgraph cluster_0"));
+        assert!(graph.contains("        p1;\n        p2;\n        p3;\n    }"));
+    }
+
+    fn updates(events: Verdicts) -> Vec<Verdict> {
+        events
+            .map(|e| match e {
+                Event::Update(verdict) => verdict,
+                e => panic!("expected an update, got {e:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verdicts_example() {
+        let mut f = File::open("input/d05-e.txt").unwrap();
+        let verdicts = updates(Verdicts::new(&mut f));
+
+        assert_eq!(verdicts.len(), 6);
+        assert_eq!(verdicts[0].status, Status::Correct);
+        assert_eq!(verdicts[0].correct_sum, 61);
+        assert_eq!(verdicts[3].status, Status::Fixed(vec![97, 75, 47, 61, 53]));
+        assert_eq!(verdicts[3].fixed_sum, 47);
+
+        let last = verdicts.last().unwrap();
+        assert_eq!((last.correct_sum, last.fixed_sum), (143, 123));
+    }
+
+    #[test]
+    fn test_verdicts_full() {
+        let mut f = File::open("input/d05-f.txt").unwrap();
+        let last = updates(Verdicts::new(&mut f)).pop().unwrap();
+        assert_eq!((last.correct_sum, last.fixed_sum), (6267, 5184));
+    }
+
+    #[test]
+    fn test_verdicts_lazy() {
+        let input = "1|2\n2|3\n\n3,2,1\n1,2\n2|1\n";
+        let mut bytes = input.as_bytes();
+        let mut verdicts = Verdicts::new(&mut bytes);
+        assert_eq!(
+            verdicts.next(),
+            Some(Event::Update(Verdict {
+                update: vec![3, 2, 1],
+                status: Status::Fixed(vec![1, 2, 3]),
+                correct_sum: 0,
+                fixed_sum: 2,
+            }))
+        );
+        assert_eq!(
+            verdicts.next(),
+            Some(Event::Update(Verdict {
+                update: vec![1, 2],
+                status: Status::Correct,
+                correct_sum: 2,
+                fixed_sum: 2,
+            }))
+        );
+        assert_eq!(verdicts.next(), Some(Event::LateRule(2, 1)));
+        assert_eq!(verdicts.next(), None);
+    }
+
+    #[test]
+    fn test_large_sums() {
+        let input = "1|3000000000\n\n1,3000000000,2\n1,3000000000,2\n3000000000,1,2\n";
+        assert_eq!(part1(&mut input.as_bytes()), 6000000000);
+        assert_eq!(part2(&mut input.as_bytes()), Ok(3000000000));
+
+        let last = updates(Verdicts::new(&mut input.as_bytes())).pop().unwrap();
+        assert_eq!((last.correct_sum, last.fixed_sum), (6000000000, 3000000000));
+    }
+
+    #[test]
+    fn test_load_interleaved() {
+        let input = "1000|2000\n\n\n1000,3000,2000\n3000|1000\n\n\n\n2000,3000\n";
+        let p = Print::load(&mut input.as_bytes());
+        assert_eq!(p.ordering_rules.len(), 2);
+        assert_eq!(p.updates, vec![vec![1000, 3000, 2000], vec![2000, 3000]]);
+        assert_eq!(
+            topo_sort(&p.ordering_rules, &p.updates[0]),
+            Ordered::Unique(vec![3000, 1000, 2000])
+        );
+    }
+
+    #[test]
+    fn test_topo_sort() {
+        let r = rules(&[(1, 2), (2, 3), (1, 3)]);
+        assert_eq!(topo_sort(&r, &[3, 1, 2]), Ordered::Unique(vec![1, 2, 3]));
+
+        let r = rules(&[(1, 3), (2, 3)]);
+        assert_eq!(topo_sort(&r, &[3, 2, 1]), Ordered::Ambiguous(vec![2, 1, 3]));
+        assert!(is_correct_update(&r, &[2, 1, 3]));
+        assert!(is_correct_update(&r, &[1, 2, 3]));
+        assert!(!is_correct_update(&r, &[1, 3, 2]));
+
+        let r = rules(&[(1, 2), (2, 4), (4, 1), (3, 1)]);
+        assert_eq!(topo_sort(&r, &[3, 4, 2, 1]), Ordered::Cycle(vec![1, 2, 4]));
+        assert!(matches!(
+            topo_sort(&r, &[1, 2, 4, 5]),
+            Ordered::Cycle(c) if c.len() == 3
+        ));
+    }
+
+    #[test]
+    fn test_violations_example() {
+        let mut f = File::open("input/d05-e.txt").unwrap();
+        let p = Print::load(&mut f);
+
+        assert_eq!(
+            violations(&p.ordering_rules, &[75, 97, 47, 61, 53]),
+            vec![Violation {
+                before: 97,
+                after: 75,
+                before_pos: 1,
+                after_pos: 0
+            }]
+        );
+        assert_eq!(violations(&p.ordering_rules, &[61, 13, 29]).len(), 1);
+        assert_eq!(
+            violations(&p.ordering_rules, &[97, 13, 75, 29, 47]).len(),
+            4
+        );
+        for u in p.updates.iter() {
+            assert_eq!(
+                violations(&p.ordering_rules, u).is_empty(),
+                is_correct_update(&p.ordering_rules, u)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixing_moves() {
+        assert_eq!(
+            fixing_moves(&[75, 97, 47, 61, 53], &[97, 75, 47, 61, 53]),
+            vec![Move {
+                page: 97,
+                from: 1,
+                to: 0
+            }]
+        );
+        assert_eq!(
+            fixing_moves(&[97, 13, 75, 29, 47], &[97, 75, 47, 29, 13]),
+            vec![
+                Move {
+                    page: 13,
+                    from: 1,
+                    to: 4
+                },
+                Move {
+                    page: 47,
+                    from: 4,
+                    to: 2
+                }
+            ]
+        );
+        assert!(fixing_moves(&[1, 2, 3], &[1, 2, 3]).is_empty());
+        assert_eq!(fixing_moves(&[3, 2, 1], &[1, 2, 3]).len(), 2);
+    }
+
+    #[test]
+    fn test_cycle_display() {
+        assert_eq!(
+            Cycle(vec![1, 2, 4]).to_string(),
+            "rules form a cycle: 1 -> 2 -> 4 -> 1"
+        );
+    }
+
+    #[test]
+    fn test_topo_sort_example() {
+        let mut f = File::open("input/d05-e.txt").unwrap();
+        let p = Print::load(&mut f);
+        assert_eq!(
+            topo_sort(&p.ordering_rules, &[97, 13, 75, 29, 47]),
+            Ordered::Unique(vec![97, 75, 47, 29, 13])
+        );
+        for u in p.updates.iter() {
+            assert!(matches!(
+                topo_sort(&p.ordering_rules, u),
+                Ordered::Unique(_)
+            ));
+        }
+    }
+
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d05-e.txt").unwrap();
@@ -130,13 +906,13 @@ mod tests {
     fn test_part2_example() {
         let mut f = File::open("input/d05-e.txt").unwrap();
         let safe_reports = part2(&mut f);
-        assert_eq!(safe_reports, 123);
+        assert_eq!(safe_reports, Ok(123));
     }
 
     #[test]
     fn test_part2_full() {
         let mut f = File::open("input/d05-f.txt").unwrap();
         let safe_reports = part2(&mut f);
-        assert_eq!(safe_reports, 5184);
+        assert_eq!(safe_reports, Ok(5184));
     }
 }
@@ -1,5 +1,6 @@
+use crate::reader;
 use std::collections::HashSet;
-use std::io::{BufRead, BufReader, Read};
+use std::io::Read;
 use std::iter::successors;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -45,52 +46,49 @@ impl Map {
 }
 
 fn load(input: &mut dyn Read) -> (Guard, Map) {
-    let (width, places, guard) = BufReader::new(input)
-        .lines()
-        .map(|s| s.unwrap().bytes().collect::<Vec<u8>>())
-        .fold(
-            (
-                0,
-                Vec::new(),
-                Guard {
-                    pos: Pos { col: 0, row: 0 },
-                    spd: Speed { right: 1, down: 0 },
-                },
-            ),
-            |(width, mut places, g), line| {
-                let width = width.max(line.len());
-
-                let guard = line
-                    .iter()
-                    .copied()
-                    .enumerate()
-                    .find(|(_, b)| b"^v<>".contains(b))
-                    .map(|(offset, b)| {
-                        let row = (places.len() / width) as i16;
-                        let col = offset as i16;
-
-                        let (down, right) = match b {
-                            b'^' => (-1, 0),
-                            b'v' => (1, 0),
-                            b'<' => (0, -1),
-                            b'>' => (0, 1),
-                            _ => panic!("not expected to happen"),
-                        };
-
-                        Guard {
-                            pos: Pos { row, col },
-                            spd: Speed { down, right },
-                        }
-                    })
-                    .unwrap_or(g);
-
-                places.extend(line.into_iter().map(|b| match b {
-                    b'#' => Place::Obstruction,
-                    _ => Place::Traversable,
-                }));
-                (width, places, guard)
+    let (width, places, guard) = reader::grid(input).into_iter().fold(
+        (
+            0,
+            Vec::new(),
+            Guard {
+                pos: Pos { col: 0, row: 0 },
+                spd: Speed { right: 1, down: 0 },
             },
-        );
+        ),
+        |(width, mut places, g), line| {
+            let width = width.max(line.len());
+
+            let guard = line
+                .iter()
+                .copied()
+                .enumerate()
+                .find(|(_, b)| b"^v<>".contains(b))
+                .map(|(offset, b)| {
+                    let row = (places.len() / width) as i16;
+                    let col = offset as i16;
+
+                    let (down, right) = match b {
+                        b'^' => (-1, 0),
+                        b'v' => (1, 0),
+                        b'<' => (0, -1),
+                        b'>' => (0, 1),
+                        _ => panic!("not expected to happen"),
+                    };
+
+                    Guard {
+                        pos: Pos { row, col },
+                        spd: Speed { down, right },
+                    }
+                })
+                .unwrap_or(g);
+
+            places.extend(line.into_iter().map(|b| match b {
+                b'#' => Place::Obstruction,
+                _ => Place::Traversable,
+            }));
+            (width, places, guard)
+        },
+    );
 
     (guard, Map { width, places })
 }
@@ -120,10 +118,7 @@ fn step(g: Guard, m: &Map) -> Guard {
     let (spd, pos) = step_candidates(g.spd)
         .into_iter()
         .map(|spd| (spd, forward(g.pos, spd)))
-        .find(|(_, pos)| match m.get(*pos) {
-            Place::Obstruction => false,
-            _ => true,
-        })
+        .find(|(_, pos)| !matches!(m.get(*pos), Place::Obstruction))
         .expect("Did not find a suitable direction");
 
     Guard { spd, pos }
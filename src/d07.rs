@@ -1,4 +1,5 @@
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::io::Read;
 use std::iter::successors;
 
 fn int_sz(n: u64) -> u32 {
@@ -116,10 +117,7 @@ fn check_params_and_ops(params: &[u64], ops: &[Op], result: u64) -> bool {
 fn part(ops: Vec<Op>, input: &mut dyn Read) -> u64 {
     use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-    let lines = BufReader::new(input)
-        .lines()
-        .map(|l| l.unwrap())
-        .collect::<Vec<String>>();
+    let lines = reader::lines(input).collect::<Vec<String>>();
 
     lines
         .into_par_iter()
@@ -183,31 +181,25 @@ mod tests {
 
     #[test]
     fn test_check_2() {
-        assert_eq!(
-            check_params_and_ops(
-                &[89, 4, 78, 6, 1],
-                &[Op::Mul, Op::Add, Op::Mul, Op::Add],
-                2604
-            ),
-            false
-        );
+        assert!(!check_params_and_ops(
+            &[89, 4, 78, 6, 1],
+            &[Op::Mul, Op::Add, Op::Mul, Op::Add],
+            2604
+        ));
     }
 
     #[test]
     fn test_op_candidates_v1_iter_3() {
-        let expected: HashSet<Vec<Op>> = HashSet::from_iter(
-            vec![
-                vec![Op::Add, Op::Add, Op::Add],
-                vec![Op::Add, Op::Add, Op::Mul],
-                vec![Op::Add, Op::Mul, Op::Add],
-                vec![Op::Add, Op::Mul, Op::Mul],
-                vec![Op::Mul, Op::Add, Op::Add],
-                vec![Op::Mul, Op::Add, Op::Mul],
-                vec![Op::Mul, Op::Mul, Op::Add],
-                vec![Op::Mul, Op::Mul, Op::Mul],
-            ]
-            .into_iter(),
-        );
+        let expected: HashSet<Vec<Op>> = HashSet::from_iter(vec![
+            vec![Op::Add, Op::Add, Op::Add],
+            vec![Op::Add, Op::Add, Op::Mul],
+            vec![Op::Add, Op::Mul, Op::Add],
+            vec![Op::Add, Op::Mul, Op::Mul],
+            vec![Op::Mul, Op::Add, Op::Add],
+            vec![Op::Mul, Op::Add, Op::Mul],
+            vec![Op::Mul, Op::Mul, Op::Add],
+            vec![Op::Mul, Op::Mul, Op::Mul],
+        ]);
         let actual: HashSet<Vec<Op>> =
             HashSet::from_iter(Candidates::with_ops_and_count(vec![Op::Add, Op::Mul], 3));
 
@@ -216,20 +208,17 @@ mod tests {
 
     #[test]
     fn test_op_candidatesv2_iter_2() {
-        let expected: HashSet<Vec<Op>> = HashSet::from_iter(
-            vec![
-                vec![Op::Add, Op::Add],
-                vec![Op::Add, Op::Mul],
-                vec![Op::Add, Op::Cat],
-                vec![Op::Mul, Op::Add],
-                vec![Op::Mul, Op::Mul],
-                vec![Op::Mul, Op::Cat],
-                vec![Op::Cat, Op::Add],
-                vec![Op::Cat, Op::Mul],
-                vec![Op::Cat, Op::Cat],
-            ]
-            .into_iter(),
-        );
+        let expected: HashSet<Vec<Op>> = HashSet::from_iter(vec![
+            vec![Op::Add, Op::Add],
+            vec![Op::Add, Op::Mul],
+            vec![Op::Add, Op::Cat],
+            vec![Op::Mul, Op::Add],
+            vec![Op::Mul, Op::Mul],
+            vec![Op::Mul, Op::Cat],
+            vec![Op::Cat, Op::Add],
+            vec![Op::Cat, Op::Mul],
+            vec![Op::Cat, Op::Cat],
+        ]);
         let actual: HashSet<Vec<Op>> = HashSet::from_iter(Candidates::with_ops_and_count(
             vec![Op::Add, Op::Mul, Op::Cat],
             2,
@@ -241,9 +230,8 @@ mod tests {
     #[test]
     fn test_size() {
         let mut f = File::open("input/d07-f.txt").unwrap();
-        let max_len = BufReader::new(&mut f)
-            .lines()
-            .map(|l| equation_from_line(&l.unwrap()).1.len())
+        let max_len = reader::lines(&mut f)
+            .map(|l| equation_from_line(&l).1.len())
             .max()
             .unwrap();
         assert_eq!(max_len, 12);
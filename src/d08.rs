@@ -1,16 +1,18 @@
-use std::collections::HashSet;
-use std::io::{BufRead, BufReader, Read};
+use crate::reader;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::iter::successors;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 struct Pos {
-    row: i16,
-    col: i16,
+    row: i32,
+    col: i32,
 }
 
 struct World {
-    width: i16,
-    height: i16,
-    antennas: Vec<(char, Vec<Pos>)>,
+    width: i32,
+    height: i32,
+    antennas: HashMap<char, Vec<Pos>>,
 }
 
 impl World {
@@ -19,65 +21,19 @@ impl World {
     }
 }
 
-fn find_freq(freq: char, antennas: &[(char, Vec<Pos>)]) -> Vec<Pos> {
-    antennas
-        .iter()
-        .find_map(|(f, pp)| if freq == *f { Some(pp.clone()) } else { None })
-        .unwrap_or(Vec::new())
-}
-
-fn merge(a: Vec<(char, Vec<Pos>)>, b: Vec<(char, Vec<Pos>)>) -> Vec<(char, Vec<Pos>)> {
-    let freqs = a
-        .iter()
-        .map(|(f, _)| *f)
-        .chain(b.iter().map(|(f, _)| *f))
-        .collect::<HashSet<char>>();
-
-    freqs
-        .into_iter()
-        .map(|freq| {
-            (
-                freq,
-                find_freq(freq, &a)
-                    .into_iter()
-                    .chain(find_freq(freq, &b).into_iter())
-                    .collect(),
-            )
-        })
-        .collect()
-}
-
 fn load(input: &mut dyn Read) -> World {
-    let (height, width, antennas) = BufReader::new(input)
-        .lines()
-        .map(|l| l.unwrap())
-        .enumerate()
-        .fold(
-            (0, 0, Vec::new()),
-            |(height, width, antennas), (row, line)| {
-                let row_antennas =
-                    line.chars()
-                        .enumerate()
-                        .fold(Vec::new(), |row_antennas, (col, c)| match c {
-                            '.' => row_antennas,
-                            c => merge(
-                                row_antennas,
-                                vec![(
-                                    c,
-                                    vec![Pos {
-                                        row: row as i16,
-                                        col: col as i16,
-                                    }],
-                                )],
-                            ),
-                        });
-                (
-                    height + 1,
-                    width.max(line.len() as i16),
-                    merge(antennas, row_antennas),
-                )
-            },
-        );
+    let (height, width, antennas) = reader::lines(input).enumerate().fold(
+        (0, 0, HashMap::new()),
+        |(height, width, mut antennas), (row, line)| {
+            for (col, c) in line.chars().enumerate().filter(|(_, c)| *c != '.') {
+                antennas.entry(c).or_insert_with(Vec::new).push(Pos {
+                    row: row as i32,
+                    col: col as i32,
+                });
+            }
+            (height + 1, width.max(line.len() as i32), antennas)
+        },
+    );
     World {
         width,
         height,
@@ -86,8 +42,8 @@ fn load(input: &mut dyn Read) -> World {
 }
 
 fn antinodes2(
-    height: i16,
-    width: i16,
+    height: i32,
+    width: i32,
     Pos {
         row: row0,
         col: col0,
@@ -115,33 +71,44 @@ fn antinodes2(
     .collect()
 }
 
-fn antinodesn(
-    height: i16,
-    width: i16,
-    Pos {
-        row: row0,
-        col: col0,
-    }: Pos,
-    Pos {
-        row: row1,
-        col: col1,
-    }: Pos,
-) -> Vec<Pos> {
-    (0..height)
-        .flat_map(|row| (0..width).map(move |col| Pos { col, row }))
-        .filter(|Pos { row, col }| {
-            let dcol = col1 - col0;
-            let drow = row1 - row0;
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
 
-            drow * (col - col0) + dcol * (row0 - row) == 0
+fn antinodesn(height: i32, width: i32, p0: Pos, p1: Pos) -> Vec<Pos> {
+    let drow = p1.row - p0.row;
+    let dcol = p1.col - p0.col;
+    let g = gcd(drow, dcol);
+    let (drow, dcol) = (drow / g, dcol / g);
+
+    let contains = |Pos { row, col }: &Pos| *col >= 0 && *row >= 0 && *col < width && *row < height;
+    let forward = successors(Some(p0), |p| {
+        Some(Pos {
+            row: p.row + drow,
+            col: p.col + dcol,
         })
-        .collect()
+    })
+    .take_while(contains);
+    let backward = successors(Some(p0), |p| {
+        Some(Pos {
+            row: p.row - drow,
+            col: p.col - dcol,
+        })
+    })
+    .skip(1)
+    .take_while(contains);
+
+    forward.chain(backward).collect()
 }
 
 fn antinodes(
-    height: i16,
-    width: i16,
-    an_for_pair: fn(i16, i16, Pos, Pos) -> Vec<Pos>,
+    height: i32,
+    width: i32,
+    an_for_pair: fn(i32, i32, Pos, Pos) -> Vec<Pos>,
     antennas: &[Pos],
 ) -> Vec<Pos> {
     antennas
@@ -164,14 +131,84 @@ fn antinodes(
         .collect()
 }
 
-fn part1(input: &mut dyn Read) -> u32 {
-    let w = load(input);
+fn freq_antinodes(
+    w: &World,
+    an_for_pair: fn(i32, i32, Pos, Pos) -> Vec<Pos>,
+) -> Vec<(char, HashSet<Pos>)> {
+    let mut by_freq = w
+        .antennas
+        .iter()
+        .map(|(freq, antennas)| {
+            (
+                *freq,
+                antinodes(w.height, w.width, an_for_pair, antennas)
+                    .into_iter()
+                    .filter(|p| w.contains(*p))
+                    .collect::<HashSet<Pos>>(),
+            )
+        })
+        .collect::<Vec<(char, HashSet<Pos>)>>();
+    by_freq.sort_by_key(|(freq, _)| *freq);
+    by_freq
+}
 
-    let antinodes = w
+struct FreqStats {
+    freq: char,
+    antennas: usize,
+    antinodes: usize,
+    shared: usize,
+}
+
+fn freq_stats(w: &World, by_freq: &[(char, HashSet<Pos>)]) -> Vec<FreqStats> {
+    let mut producers: HashMap<Pos, u32> = HashMap::new();
+    for (_, nodes) in by_freq.iter() {
+        for p in nodes.iter() {
+            *producers.entry(*p).or_insert(0) += 1;
+        }
+    }
+
+    by_freq
+        .iter()
+        .map(|(freq, nodes)| FreqStats {
+            freq: *freq,
+            antennas: w.antennas[freq].len(),
+            antinodes: nodes.len(),
+            shared: nodes.iter().filter(|p| producers[*p] > 1).count(),
+        })
+        .collect()
+}
+
+fn render(w: &World, antinodes: &HashSet<Pos>, freqs: Option<&str>) -> String {
+    let antennas = w
         .antennas
         .iter()
-        .flat_map(|(_, antennas)| antinodes(w.height, w.width, antinodes2, antennas))
-        .filter(|p| w.contains(*p))
+        .filter(|(freq, _)| freqs.is_none_or(|fs| fs.contains(**freq)))
+        .flat_map(|(freq, pp)| pp.iter().map(|p| (*p, *freq)))
+        .collect::<HashMap<Pos, char>>();
+
+    (0..w.height)
+        .map(|row| {
+            (0..w.width)
+                .map(|col| {
+                    let pos = Pos { row, col };
+                    if antinodes.contains(&pos) {
+                        '#'
+                    } else {
+                        antennas.get(&pos).copied().unwrap_or('.')
+                    }
+                })
+                .chain(['\n'])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn part1(input: &mut dyn Read) -> u32 {
+    let w = load(input);
+
+    let antinodes = freq_antinodes(&w, antinodes2)
+        .into_iter()
+        .flat_map(|(_, nodes)| nodes)
         .collect::<HashSet<Pos>>();
     antinodes.len() as u32
 }
@@ -179,11 +216,9 @@ fn part1(input: &mut dyn Read) -> u32 {
 fn part2(input: &mut dyn Read) -> u32 {
     let w = load(input);
 
-    let antinodes = w
-        .antennas
-        .iter()
-        .flat_map(|(_, antennas)| antinodes(w.height, w.width, antinodesn, antennas))
-        .filter(|p| w.contains(*p))
+    let antinodes = freq_antinodes(&w, antinodesn)
+        .into_iter()
+        .flat_map(|(_, nodes)| nodes)
         .collect::<HashSet<Pos>>();
     antinodes.len() as u32
 }
@@ -196,6 +231,39 @@ pub fn run_part2(input: &mut dyn Read) {
     println!("{}", part2(input));
 }
 
+pub fn run_map(input: &mut dyn Read, args: &[String]) {
+    let an_for_pair = match args.first().map(|a| a.as_str()) {
+        None | Some("p1") => antinodes2,
+        Some("p2") => antinodesn,
+        Some(other) => panic!("expected p1 or p2, got {other}"),
+    };
+    let freqs = args.get(1).map(|a| a.as_str());
+
+    let w = load(input);
+    let by_freq = freq_antinodes(&w, an_for_pair);
+    let stats = freq_stats(&w, &by_freq);
+
+    let antinodes = by_freq
+        .into_iter()
+        .filter(|(freq, _)| freqs.is_none_or(|fs| fs.contains(*freq)))
+        .flat_map(|(_, nodes)| nodes)
+        .collect::<HashSet<Pos>>();
+
+    print!("{}", render(&w, &antinodes, freqs));
+    println!();
+    println!("freq antennas antinodes shared");
+    for st in stats
+        .iter()
+        .filter(|st| freqs.is_none_or(|fs| fs.contains(st.freq)))
+    {
+        println!(
+            "{:>4} {:>8} {:>9} {:>6}",
+            st.freq, st.antennas, st.antinodes, st.shared
+        );
+    }
+    println!("unique antinodes: {}", antinodes.len());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,7 +277,7 @@ mod tests {
         assert_eq!(world.height, 12);
         assert_eq!(world.antennas.len(), 2);
         assert_eq!(
-            world.antennas.iter().map(|(_, aa)| aa.len()).max(),
+            world.antennas.values().map(|aa| aa.len()).max(),
             Some(4usize)
         );
     }
@@ -222,11 +290,38 @@ mod tests {
         assert_eq!(world.height, 50);
         assert_eq!(world.antennas.len(), 62);
         assert_eq!(
-            world.antennas.iter().map(|(_, aa)| aa.len()).max(),
+            world.antennas.values().map(|aa| aa.len()).max(),
             Some(4usize)
         );
     }
 
+    #[test]
+    fn test_load_many_antennas() {
+        let side = 300;
+        let freqs = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let map = (0..side)
+            .map(|row| {
+                (0..side)
+                    .map(|col| freqs[(row * 7 + col) % freqs.len()] as char)
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let world = load(&mut map.as_bytes());
+        assert_eq!(world.width, side as i32);
+        assert_eq!(world.height, side as i32);
+        assert_eq!(world.antennas.len(), freqs.len());
+        assert_eq!(
+            world.antennas.values().map(|aa| aa.len()).sum::<usize>(),
+            side * side
+        );
+        assert_eq!(
+            world.antennas[&'0'][..2],
+            [Pos { row: 0, col: 0 }, Pos { row: 0, col: 62 }]
+        );
+    }
+
     #[test]
     fn test_antinodes2() {
         assert_eq!(
@@ -260,6 +355,85 @@ mod tests {
         assert_eq!(nodes, expected);
     }
 
+    fn antinodesn_scan(height: i32, width: i32, p0: Pos, p1: Pos) -> Vec<Pos> {
+        (0..height)
+            .flat_map(|row| (0..width).map(move |col| Pos { col, row }))
+            .filter(|Pos { row, col }| {
+                let dcol = p1.col - p0.col;
+                let drow = p1.row - p0.row;
+
+                drow * (col - p0.col) + dcol * (p0.row - row) == 0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_antinodesn_matches_scan() {
+        let (height, width) = (7, 9);
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| Pos { row, col }))
+            .collect::<Vec<Pos>>();
+
+        for p0 in cells.iter().copied() {
+            for p1 in cells.iter().copied().filter(|p1| *p1 != p0) {
+                let stepped = antinodesn(height, width, p0, p1);
+                assert_eq!(
+                    stepped.len(),
+                    stepped.iter().collect::<HashSet<&Pos>>().len()
+                );
+                assert_eq!(
+                    stepped.into_iter().collect::<HashSet<Pos>>(),
+                    antinodesn_scan(height, width, p0, p1)
+                        .into_iter()
+                        .collect::<HashSet<Pos>>(),
+                    "{p0:?} {p1:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_freq_stats_example() {
+        let mut f = File::open("input/d08-e.txt").unwrap();
+        let w = load(&mut f);
+        let by_freq = freq_antinodes(&w, antinodes2);
+        let stats = freq_stats(&w, &by_freq);
+
+        assert_eq!(
+            stats
+                .iter()
+                .map(|st| (st.freq, st.antennas, st.antinodes, st.shared))
+                .collect::<Vec<_>>(),
+            vec![('0', 4, 10, 1), ('A', 3, 5, 1)]
+        );
+    }
+
+    #[test]
+    fn test_render_example() {
+        let mut f = File::open("input/d08-e.txt").unwrap();
+        let w = load(&mut f);
+        let antinodes = freq_antinodes(&w, antinodes2)
+            .into_iter()
+            .flat_map(|(_, nodes)| nodes)
+            .collect::<HashSet<Pos>>();
+
+        let expected = "\
+......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....#.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+";
+        assert_eq!(render(&w, &antinodes, None), expected);
+    }
+
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d08-e.txt").unwrap();
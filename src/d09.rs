@@ -1,6 +1,7 @@
+use crate::reader;
 use std::collections::HashMap;
 use std::io::Read;
-use std::iter::repeat;
+use std::iter::repeat_n;
 use std::ops::AddAssign;
 
 enum LoadNext {
@@ -9,27 +10,19 @@ enum LoadNext {
 }
 
 fn load(input: &mut dyn Read) -> Vec<Option<u16>> {
-    input
-        .bytes()
-        .filter_map(|b| match b {
-            Ok(n) => {
-                if n.is_ascii_digit() {
-                    Some((n - b'0') as u16)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
+    reader::bytes(input)
+        .into_iter()
+        .filter(|n| n.is_ascii_digit())
+        .map(|n| (n - b'0') as u16)
         .fold(
             (LoadNext::ContentForFile(0), Vec::new()),
             |(next, mut buf), num_items| match next {
                 LoadNext::ContentForFile(id) => {
-                    buf.extend(repeat(Some(id)).take(num_items as usize));
+                    buf.extend(repeat_n(Some(id), num_items as usize));
                     (LoadNext::SpaceAfterFile(id), buf)
                 }
                 LoadNext::SpaceAfterFile(id) => {
-                    buf.extend(repeat(None).take(num_items as usize));
+                    buf.extend(repeat_n(None, num_items as usize));
                     (LoadNext::ContentForFile(id + 1), buf)
                 }
             },
@@ -63,7 +56,7 @@ fn fragment(mut disk: Vec<Option<u16>>) -> Vec<Option<u16>> {
     disk
 }
 
-fn first_free(disk: &Vec<Option<u16>>, min_size: usize) -> Option<(usize, usize)> {
+fn first_free(disk: &[Option<u16>], min_size: usize) -> Option<(usize, usize)> {
     disk.iter()
         .copied()
         .enumerate()
@@ -84,7 +77,7 @@ fn first_free(disk: &Vec<Option<u16>>, min_size: usize) -> Option<(usize, usize)
         .find(|(_, len)| *len >= min_size)
 }
 
-fn file_map(disk: &Vec<Option<u16>>) -> HashMap<u16, (usize, usize)> {
+fn file_map(disk: &[Option<u16>]) -> HashMap<u16, (usize, usize)> {
     disk.iter()
         .copied()
         .enumerate()
@@ -112,7 +105,7 @@ fn defragment(mut disk: Vec<Option<u16>>) -> Vec<Option<u16>> {
     let mut fmap = file_map(&disk);
 
     for file_id in (0..=fmap.keys().copied().max().unwrap()).rev() {
-        let (file_pos, file_len) = fmap.get(&file_id).unwrap().clone();
+        let (file_pos, file_len) = *fmap.get(&file_id).unwrap();
         let free_pos = first_free(&disk, file_len).map(|(pos, _)| pos);
 
         let free_pos = match free_pos {
@@ -1,17 +1,96 @@
+use crate::reader;
 use std::collections::HashSet;
-use std::io::{BufRead, BufReader, Read};
+use std::io::Read;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Neighbourhood {
+    Four,
+    Eight,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Rules {
+    neighbourhood: Neighbourhood,
+    min_climb: u8,
+    max_climb: u8,
+    start: u8,
+    summit: u8,
+}
+
+impl Rules {
+    const STANDARD: Rules = Rules {
+        neighbourhood: Neighbourhood::Four,
+        min_climb: 1,
+        max_climb: 1,
+        start: 0,
+        summit: 9,
+    };
+
+    fn from_args(args: &[String]) -> Rules {
+        let rules = args.iter().filter_map(|a| a.split_once('=')).fold(
+            Rules::STANDARD,
+            |rules, (key, value)| match key {
+                "neigh" => Rules {
+                    neighbourhood: match value {
+                        "4" => Neighbourhood::Four,
+                        "8" => Neighbourhood::Eight,
+                        _ => panic!("neigh should be 4 or 8, got {value}"),
+                    },
+                    ..rules
+                },
+                "climb" => {
+                    let (min, max) = value.split_once('-').unwrap_or((value, value));
+                    Rules {
+                        min_climb: min.parse().expect("climb should be MIN-MAX"),
+                        max_climb: max.parse().expect("climb should be MIN-MAX"),
+                        ..rules
+                    }
+                }
+                "start" => Rules {
+                    start: value.parse().expect("start should be a height"),
+                    ..rules
+                },
+                "summit" => Rules {
+                    summit: value.parse().expect("summit should be a height"),
+                    ..rules
+                },
+                _ => panic!("unknown rule \"{key}\""),
+            },
+        );
+
+        // Trails must climb on every step, otherwise the recursion in
+        // reachable_tops and distinct_paths would not terminate
+        assert!(
+            rules.min_climb >= 1 && rules.min_climb <= rules.max_climb,
+            "climb range should be non-empty and at least 1"
+        );
+
+        rules
+    }
+
+    fn allows(&self, from: u8, to: u8) -> bool {
+        to > from && (self.min_climb..=self.max_climb).contains(&(to - from))
+    }
+}
 
 struct Map {
-    heights: Vec<u8>,
+    heights: Vec<Option<u8>>,
     length: u16,
     width: u16,
 }
 
 impl Map {
     fn load(input: &mut dyn Read) -> Map {
-        let (length, width, heights) = BufReader::new(input)
-            .lines()
-            .map(|l| l.unwrap().bytes().map(|b| (b - b'0')).collect::<Vec<u8>>())
+        let (length, width, heights) = reader::grid(input)
+            .into_iter()
+            .map(|l| {
+                l.into_iter()
+                    .map(|b| match b {
+                        b'0'..=b'9' => Some(b - b'0'),
+                        _ => None,
+                    })
+                    .collect::<Vec<Option<u8>>>()
+            })
             .fold((0, 0, Vec::new()), |(l, w, mut hs), b| {
                 let wnext = w.max(b.len() as u16);
                 hs.extend(b);
@@ -48,51 +127,235 @@ impl Map {
 
         n
     }
-}
 
-fn reachable_tops(map: &Map, pos: u16) -> HashSet<u16> {
-    let height = map.heights[pos as usize];
+    fn neigh8(&self, place: u16) -> Vec<u16> {
+        let mut n = self.neigh4(place);
+        let row = place / self.width;
+        let col = place % self.width;
+
+        let up = row != 0;
+        let down = row < self.length - 1;
+        let left = col != 0;
+        let right = col < self.width - 1;
+
+        if up && left {
+            n.push(place - self.width - 1);
+        }
+
+        if up && right {
+            n.push(place - self.width + 1);
+        }
+
+        if down && left {
+            n.push(place + self.width - 1);
+        }
+
+        if down && right {
+            n.push(place + self.width + 1);
+        }
+
+        n
+    }
+
+    fn steps(&self, rules: &Rules, place: u16) -> Vec<u16> {
+        let height = match self.heights[place as usize] {
+            Some(h) => h,
+            None => return Vec::new(),
+        };
+
+        let neighbours = match rules.neighbourhood {
+            Neighbourhood::Four => self.neigh4(place),
+            Neighbourhood::Eight => self.neigh8(place),
+        };
+
+        neighbours
+            .into_iter()
+            .filter(|neighbor| match self.heights[*neighbor as usize] {
+                Some(h) => rules.allows(height, h),
+                None => false,
+            })
+            .collect()
+    }
+
+    fn is_summit(&self, rules: &Rules, place: u16) -> bool {
+        self.heights[place as usize].is_some_and(|h| h >= rules.summit)
+    }
+
+    fn coords(&self, place: u16) -> (u16, u16) {
+        (place / self.width, place % self.width)
+    }
+
+    fn trailheads(&self, rules: &Rules) -> Vec<u16> {
+        (0u16..(self.heights.len() as u16))
+            .filter(|position| self.heights[*position as usize] == Some(rules.start))
+            .collect()
+    }
+}
 
-    if height >= 9 {
+fn reachable_tops(map: &Map, rules: &Rules, pos: u16) -> HashSet<u16> {
+    if map.is_summit(rules, pos) {
         return HashSet::from([pos]);
     }
 
-    map.neigh4(pos)
+    map.steps(rules, pos)
         .into_iter()
-        .filter(|neighbor| map.heights[*neighbor as usize] == height + 1)
-        .flat_map(|neighbor| reachable_tops(map, neighbor))
+        .flat_map(|neighbor| reachable_tops(map, rules, neighbor))
         .collect()
 }
 
-fn distinct_paths(map: &Map, pos: u16) -> u32 {
-    let height = map.heights[pos as usize];
-
-    if height >= 9 {
+fn distinct_paths(map: &Map, rules: &Rules, pos: u16) -> u32 {
+    if map.is_summit(rules, pos) {
         return 1;
     }
 
-    map.neigh4(pos)
+    map.steps(rules, pos)
         .into_iter()
-        .filter(|neighbor| map.heights[*neighbor as usize] == height + 1)
-        .map(|neighbor| distinct_paths(map, neighbor))
+        .map(|neighbor| distinct_paths(map, rules, neighbor))
         .sum()
 }
 
+fn paths_from(
+    map: &Map,
+    rules: &Rules,
+    pos: u16,
+    max_paths: usize,
+    trail: &mut Vec<u16>,
+    out: &mut Vec<Vec<u16>>,
+) {
+    if out.len() >= max_paths {
+        return;
+    }
+
+    trail.push(pos);
+
+    if map.is_summit(rules, pos) {
+        out.push(trail.clone());
+    } else {
+        for neighbor in map.steps(rules, pos) {
+            paths_from(map, rules, neighbor, max_paths, trail, out);
+        }
+    }
+
+    trail.pop();
+}
+
+struct Trailhead {
+    pos: u16,
+    score: u32,
+    rating: u32,
+    paths: Vec<Vec<u16>>,
+}
+
+fn trails(map: &Map, rules: &Rules, max_paths: usize) -> Vec<Trailhead> {
+    map.trailheads(rules)
+        .into_iter()
+        .map(|pos| {
+            let mut paths = Vec::new();
+            paths_from(map, rules, pos, max_paths, &mut Vec::new(), &mut paths);
+
+            Trailhead {
+                pos,
+                score: reachable_tops(map, rules, pos).len() as u32,
+                rating: distinct_paths(map, rules, pos),
+                paths,
+            }
+        })
+        .collect()
+}
+
+/// Places on any trail: reachable from a trailhead and leading on to a
+/// summit. Every step climbs, so joining the two halves is always a trail.
+fn trail_places(map: &Map, rules: &Rules) -> HashSet<u16> {
+    fn leads_to_summit(map: &Map, rules: &Rules, pos: u16, memo: &mut [Option<bool>]) -> bool {
+        if let Some(known) = memo[pos as usize] {
+            return known;
+        }
+        let leads = map.is_summit(rules, pos)
+            || map
+                .steps(rules, pos)
+                .into_iter()
+                .any(|next| leads_to_summit(map, rules, next, memo));
+        memo[pos as usize] = Some(leads);
+        leads
+    }
+
+    let mut memo = vec![None; map.heights.len()];
+    let mut on_trail = HashSet::new();
+    let mut todo = map
+        .trailheads(rules)
+        .into_iter()
+        .filter(|&pos| leads_to_summit(map, rules, pos, &mut memo))
+        .collect::<Vec<u16>>();
+    while let Some(pos) = todo.pop() {
+        if on_trail.insert(pos) {
+            todo.extend(
+                map.steps(rules, pos)
+                    .into_iter()
+                    .filter(|&next| leads_to_summit(map, rules, next, &mut memo)),
+            );
+        }
+    }
+    on_trail
+}
+
+fn overlay(map: &Map, rules: &Rules) -> String {
+    let on_trail = trail_places(map, rules);
+
+    (0..map.length)
+        .map(|row| {
+            (0..map.width)
+                .map(|col| {
+                    let place = row * map.width + col;
+                    match map.heights[place as usize] {
+                        Some(h) if on_trail.contains(&place) => (b'0' + h) as char,
+                        _ => '.',
+                    }
+                })
+                .chain(['\n'])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn dot(map: &Map, rules: &Rules) -> String {
+    let mut out = String::from("digraph trails {\n");
+
+    for place in 0u16..(map.heights.len() as u16) {
+        let (row, col) = map.coords(place);
+        let height = match map.heights[place as usize] {
+            Some(h) => h,
+            None => continue,
+        };
+        out.push_str(&format!(
+            "    p{place} [label=\"{height}\\n({row},{col})\"];\n"
+        ));
+
+        for neighbor in map.steps(rules, place) {
+            out.push_str(&format!("    p{place} -> p{neighbor};\n"));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
 fn part1(input: &mut dyn Read) -> u32 {
     let map = Map::load(input);
+    let rules = Rules::STANDARD;
 
-    (0u16..(map.heights.len() as u16))
-        .filter(|position| map.heights[*position as usize] == 0)
-        .map(|starting_position| reachable_tops(&map, starting_position).len() as u32)
+    map.trailheads(&rules)
+        .into_iter()
+        .map(|starting_position| reachable_tops(&map, &rules, starting_position).len() as u32)
         .sum()
 }
 
 fn part2(input: &mut dyn Read) -> u32 {
     let map = Map::load(input);
+    let rules = Rules::STANDARD;
 
-    (0u16..(map.heights.len() as u16))
-        .filter(|position| map.heights[*position as usize] == 0)
-        .map(|starting_position| distinct_paths(&map, starting_position))
+    map.trailheads(&rules)
+        .into_iter()
+        .map(|starting_position| distinct_paths(&map, &rules, starting_position))
         .sum()
 }
 
@@ -104,11 +367,196 @@ pub fn run_part2(input: &mut dyn Read) {
     println!("{}", part2(input));
 }
 
+pub fn run_trails(input: &mut dyn Read, args: &[String]) {
+    let max_paths = args
+        .iter()
+        .find(|a| !a.contains('='))
+        .map(|a| a.parse::<usize>().expect("max-paths should be a number"))
+        .unwrap_or(usize::MAX);
+    let rules = Rules::from_args(args);
+
+    let map = Map::load(input);
+    let trailheads = trails(&map, &rules, max_paths);
+
+    for t in trailheads.iter() {
+        println!(
+            "trailhead {:?}: score {}, rating {}",
+            map.coords(t.pos),
+            t.score,
+            t.rating
+        );
+        for path in t.paths.iter() {
+            let coords = path
+                .iter()
+                .map(|p| format!("{:?}", map.coords(*p)))
+                .collect::<Vec<String>>();
+            println!("    {}", coords.join(" "));
+        }
+        if t.paths.len() < t.rating as usize {
+            println!("    ... {} more", t.rating as usize - t.paths.len());
+        }
+    }
+    println!(
+        "total: score {}, rating {}",
+        trailheads.iter().map(|t| t.score).sum::<u32>(),
+        trailheads.iter().map(|t| t.rating).sum::<u32>()
+    );
+    println!();
+    print!("{}", overlay(&map, &rules));
+}
+
+pub fn run_dot(input: &mut dyn Read, args: &[String]) {
+    print!("{}", dot(&Map::load(input), &Rules::from_args(args)));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::File;
 
+    #[test]
+    fn test_trails_example() {
+        let mut f = File::open("input/d10-e.txt").unwrap();
+        let map = Map::load(&mut f);
+        let trailheads = trails(&map, &Rules::STANDARD, usize::MAX);
+
+        assert_eq!(trailheads.len(), 9);
+        assert_eq!(trailheads.iter().map(|t| t.score).sum::<u32>(), 36);
+        assert_eq!(trailheads.iter().map(|t| t.paths.len()).sum::<usize>(), 81);
+
+        let first = &trailheads[0];
+        assert_eq!(map.coords(first.pos), (0, 2));
+        assert_eq!(first.rating, 20);
+        for path in first.paths.iter() {
+            assert_eq!(path.len(), 10);
+            assert_eq!(map.heights[*path.last().unwrap() as usize], Some(9));
+        }
+    }
+
+    #[test]
+    fn test_trails_capped() {
+        let mut f = File::open("input/d10-e.txt").unwrap();
+        let map = Map::load(&mut f);
+        let trailheads = trails(&map, &Rules::STANDARD, 3);
+
+        assert!(trailheads.iter().all(|t| t.paths.len() <= 3));
+        assert_eq!(trailheads.iter().map(|t| t.rating).sum::<u32>(), 81);
+    }
+
+    #[test]
+    fn test_trail_places() {
+        let mut f = File::open("input/d10-e.txt").unwrap();
+        let map = Map::load(&mut f);
+        let on_paths = trails(&map, &Rules::STANDARD, usize::MAX)
+            .iter()
+            .flat_map(|t| t.paths.iter().flatten().copied())
+            .collect::<HashSet<u16>>();
+        assert_eq!(trail_places(&map, &Rules::STANDARD), on_paths);
+
+        let shown = overlay(&map, &Rules::STANDARD);
+        assert_eq!(shown.lines().count(), 8);
+        assert_eq!(
+            shown.chars().filter(|c| c.is_ascii_digit()).count(),
+            on_paths.len()
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let mut f = File::open("input/d10-e.txt").unwrap();
+        let map = Map::load(&mut f);
+        let graph = dot(&map, &Rules::STANDARD);
+
+        assert!(graph.starts_with("digraph trails {"));
+        assert!(graph.contains("p2 -> p10;"));
+        assert!(!graph.contains("p1 -> p0;"));
+    }
+
+    #[test]
+    fn test_load_impassable() {
+        let mut f = File::open("input/d10-e2.txt").unwrap();
+        let map = Map::load(&mut f);
+        assert_eq!(map.width, 7);
+        assert_eq!(map.length, 7);
+        assert_eq!(map.heights[0], None);
+        assert_eq!(map.heights[3], Some(0));
+    }
+
+    #[test]
+    fn test_impassable_examples() {
+        let rules = Rules::STANDARD;
+
+        let mut f = File::open("input/d10-e2.txt").unwrap();
+        let map = Map::load(&mut f);
+        let trailheads = trails(&map, &rules, usize::MAX);
+        assert_eq!(trailheads.iter().map(|t| t.score).sum::<u32>(), 2);
+
+        let mut f = File::open("input/d10-e3.txt").unwrap();
+        let map = Map::load(&mut f);
+        let trailheads = trails(&map, &rules, usize::MAX);
+        assert_eq!(trailheads.iter().map(|t| t.rating).sum::<u32>(), 3);
+    }
+
+    #[test]
+    fn test_rules_from_args() {
+        let args = ["neigh=8", "climb=1-2", "summit=8"].map(String::from);
+        assert_eq!(
+            Rules::from_args(&args),
+            Rules {
+                neighbourhood: Neighbourhood::Eight,
+                min_climb: 1,
+                max_climb: 2,
+                start: 0,
+                summit: 8,
+            }
+        );
+        assert_eq!(Rules::from_args(&[]), Rules::STANDARD);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut f = File::open("input/d10-e.txt").unwrap();
+        let map = Map::load(&mut f);
+
+        let eight = Rules {
+            neighbourhood: Neighbourhood::Eight,
+            ..Rules::STANDARD
+        };
+        let wide = Rules {
+            max_climb: 2,
+            ..Rules::STANDARD
+        };
+
+        let standard = trails(&map, &Rules::STANDARD, 0);
+        for rules in [eight, wide] {
+            let relaxed = trails(&map, &rules, 0);
+            assert_eq!(relaxed.len(), standard.len());
+            for (r, s) in relaxed.iter().zip(standard.iter()) {
+                assert!(r.score >= s.score);
+                assert!(r.rating >= s.rating);
+            }
+        }
+        assert!(
+            trails(&map, &eight, 0)
+                .iter()
+                .map(|t| t.rating)
+                .sum::<u32>()
+                > trails(&map, &Rules::STANDARD, 0)
+                    .iter()
+                    .map(|t| t.rating)
+                    .sum::<u32>()
+        );
+    }
+
+    #[test]
+    fn test_load_crlf() {
+        let data = std::fs::read_to_string("input/d10-e.txt").unwrap();
+        let crlf = data.replace('\n', "\r\n");
+        let map = Map::load(&mut crlf.as_bytes());
+        assert_eq!(map.heights, Map::load(&mut data.as_bytes()).heights);
+        assert_eq!(part2(&mut crlf.as_bytes()), 81);
+    }
+
     #[test]
     fn test_part1_example() {
         let mut f = File::open("input/d10-e.txt").unwrap();
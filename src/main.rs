@@ -9,15 +9,23 @@ mod d07;
 mod d08;
 mod d09;
 mod d10;
+mod reader;
+#[cfg(test)]
+mod xorshift;
 
 use std::env::args;
+use std::fs::File;
 use std::io::{stdin, Read};
 
-const CMDS: &[(&str, fn(&mut dyn Read))] = &[
+type Cmd = fn(&mut dyn Read);
+type ArgCmd = fn(&mut dyn Read, &[String]);
+
+const CMDS: &[(&str, Cmd)] = &[
     ("d00p1", d00::run_part1),
     ("d00p2", d00::run_part2),
     ("d01p1", d01::run_part1),
     ("d01p2", d01::run_part2),
+    ("d01stats", d01::run_stats),
     ("d02p1", d02::run_part1),
     ("d02p2", d02::run_part2),
     ("d03p1", d03::run_part1),
@@ -26,6 +34,9 @@ const CMDS: &[(&str, fn(&mut dyn Read))] = &[
     ("d04p2", d04::run_part2),
     ("d05p1", d05::run_part1),
     ("d05p2", d05::run_part2),
+    ("d05check", d05::run_check),
+    ("d05explain", d05::run_explain),
+    ("d05stream", d05::run_stream),
     ("d06p1", d06::run_part1),
     ("d06p2", d06::run_part2),
     ("d07p1", d07::run_part1),
@@ -38,25 +49,82 @@ const CMDS: &[(&str, fn(&mut dyn Read))] = &[
     ("d10p2", d10::run_part2),
 ];
 
-fn find_cmd(name: &str) -> Option<fn(&mut dyn Read)> {
+const ARG_CMDS: &[(&str, &str, ArgCmd)] = &[
+    (
+        "d01cols",
+        "[ref=N] [metric=abs|sq|max] [similarity=A,B]",
+        d01::run_columns,
+    ),
+    (
+        "d02policy",
+        "[--explain] [--json] [preset=p1|p2] [min=N] [max=N] [plateaus=yes|no] [dir=up|down|either] [bad=N]",
+        d02::run_policy,
+    ),
+    (
+        "d03eval",
+        "[--trace] [mul] [add] [do] [don't]",
+        d03::run_eval,
+    ),
+    (
+        "d04words",
+        "[--highlight] [--colour] [--json] [--pad=C] [word...]",
+        d04::run_words,
+    ),
+    ("d04template", "[template-file]", d04::run_template),
+    ("d05dot", "[update-number]", d05::run_dot),
+    ("d08map", "[p1|p2] [frequencies]", d08::run_map),
+    (
+        "d10trails",
+        "[max-paths] [neigh=4|8] [climb=MIN-MAX] [start=H] [summit=H]",
+        d10::run_trails,
+    ),
+    (
+        "d10dot",
+        "[neigh=4|8] [climb=MIN-MAX] [start=H] [summit=H]",
+        d10::run_dot,
+    ),
+];
+
+fn find_cmd(name: &str) -> Option<Cmd> {
     for (fun_name, fun) in CMDS.iter() {
         if name == *fun_name {
             return Some(*fun);
         }
     }
-    return None;
+    None
+}
+
+fn find_arg_cmd(name: &str) -> Option<ArgCmd> {
+    for (fun_name, _, fun) in ARG_CMDS.iter() {
+        if name == *fun_name {
+            return Some(*fun);
+        }
+    }
+    None
 }
 
 fn help(name: &str) {
-    println!("usage: {} <subcommand>", name);
+    println!("usage: {} <subcommand> [--input=PATH] [args]", name);
     println!("subcommands:");
     for (name, _) in CMDS.iter() {
         println!("    {}", name);
     }
+    for (name, usage, _) in ARG_CMDS.iter() {
+        println!("    {} {}", name, usage);
+    }
+}
+
+/// Reads the named file or stdin, decompressing either when needed
+fn open_input(path: Option<&str>) -> Box<dyn Read> {
+    let raw: Box<dyn Read> = match path {
+        Some(p) => Box::new(File::open(p).unwrap_or_else(|e| panic!("cannot open {}: {}", p, e))),
+        None => Box::new(stdin()),
+    };
+    reader::decompressed(raw)
 }
 
 fn main() {
-    let (subcmd_name, prog_name) = {
+    let (subcmd_name, prog_name, cmd_args) = {
         let mut args = args();
         let prog_name = args.next().unwrap();
 
@@ -67,9 +135,26 @@ fn main() {
             }
             Some(cmd) => cmd,
         };
-        (subcmd, prog_name)
+        (subcmd, prog_name, args.collect::<Vec<String>>())
     };
 
+    let path = cmd_args
+        .iter()
+        .find_map(|a| a.strip_prefix("--input="))
+        .map(String::from);
+    let cmd_args = cmd_args
+        .into_iter()
+        .filter(|a| !a.starts_with("--input="))
+        .collect::<Vec<String>>();
+
+    // The input is only opened once the subcommand is known, so that a typo
+    // does not sit waiting on stdin
+    if let Some(f) = find_arg_cmd(&subcmd_name) {
+        let mut input = open_input(path.as_deref());
+        f(&mut input, &cmd_args);
+        return;
+    }
+
     let run_fn = match find_cmd(&subcmd_name) {
         Some(f) => f,
         None => {
@@ -79,5 +164,6 @@ fn main() {
         }
     };
 
-    run_fn(&mut stdin())
+    let mut input = open_input(path.as_deref());
+    run_fn(&mut input)
 }
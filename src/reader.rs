@@ -0,0 +1,243 @@
+//! Input reading shared by the days, so that every loader sees the same
+//! text regardless of compression, line endings, byte order marks or trailing
+//! blank lines
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Cursor, Read};
+
+const BOM: &str = "\u{feff}";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompresses input that starts with the magic bytes of gzip or zstd and
+/// passes anything else through unchanged
+pub fn decompressed<'a>(mut input: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+    let mut magic = Vec::new();
+    input
+        .by_ref()
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .unwrap();
+
+    let gzip = magic.starts_with(GZIP_MAGIC);
+    let zstd = magic.starts_with(ZSTD_MAGIC);
+    let whole = Cursor::new(magic).chain(input);
+    if gzip {
+        Box::new(MultiGzDecoder::new(whole))
+    } else if zstd {
+        let source: Box<dyn Read + 'a> = Box::new(whole);
+        let decoder = StreamingDecoder::new(BufReader::new(source))
+            .unwrap_or_else(|e| panic!("bad zstd input: {}", e));
+        Box::new(MultiZstdDecoder {
+            decoder: Some(decoder),
+        })
+    } else {
+        Box::new(whole)
+    }
+}
+
+type ZstdFrame<'a> = StreamingDecoder<BufReader<Box<dyn Read + 'a>>, FrameDecoder>;
+
+/// Decodes zstd frames one after another until the input runs out, the way
+/// `MultiGzDecoder` does with gzip members
+struct MultiZstdDecoder<'a> {
+    decoder: Option<ZstdFrame<'a>>,
+}
+
+impl Read for MultiZstdDecoder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else {
+                return Ok(0);
+            };
+            let n = decoder.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+
+            let (mut source, frame_decoder) = self.decoder.take().unwrap().into_parts();
+            if source.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            self.decoder = Some(
+                StreamingDecoder::new_with_decoder(source, frame_decoder)
+                    .map_err(std::io::Error::other)?,
+            );
+        }
+    }
+}
+
+/// Lines without their CR or LF and without the blank lines at the end
+pub struct Lines<'a> {
+    lines: std::io::Lines<BufReader<&'a mut dyn Read>>,
+    first: bool,
+    held: VecDeque<String>,
+    ready: usize,
+}
+
+impl Iterator for Lines<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.ready > 0 {
+            self.ready -= 1;
+            return self.held.pop_front();
+        }
+
+        // Blank lines are held back until something follows them
+        loop {
+            let mut line = self.lines.next()?.unwrap();
+            if std::mem::take(&mut self.first) && line.starts_with(BOM) {
+                line.drain(..BOM.len());
+            }
+            if line.ends_with('\r') {
+                line.pop();
+            }
+
+            let blank = line.trim().is_empty();
+            self.held.push_back(line);
+            if !blank {
+                self.ready = self.held.len() - 1;
+                return self.held.pop_front();
+            }
+        }
+    }
+}
+
+pub fn lines(input: &mut dyn Read) -> Lines<'_> {
+    Lines {
+        lines: BufReader::new(input).lines(),
+        first: true,
+        held: VecDeque::new(),
+        ready: 0,
+    }
+}
+
+/// Rows of bytes, one per line
+pub fn grid(input: &mut dyn Read) -> Vec<Vec<u8>> {
+    lines(input).map(|l| l.into_bytes()).collect()
+}
+
+/// The whole input with LF line endings and no trailing newlines
+pub fn bytes(input: &mut dyn Read) -> Vec<u8> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data).unwrap();
+
+    let data = data.strip_prefix(BOM.as_bytes()).unwrap_or(&data);
+    let mut out = data
+        .iter()
+        .enumerate()
+        .filter(|&(n, &b)| !(b == b'\r' && data.get(n + 1) == Some(&b'\n')))
+        .map(|(_, &b)| b)
+        .collect::<Vec<u8>>();
+    while out.last() == Some(&b'\n') {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crlf(data: &[u8]) -> Vec<u8> {
+        BOM.bytes()
+            .chain(data.iter().flat_map(|&b| match b {
+                b'\n' => vec![b'\r', b'\n'],
+                b => vec![b],
+            }))
+            .chain(*b"\r\n\r\n")
+            .collect()
+    }
+
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        decompressed(Box::new(data)).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_decompressed() {
+        use flate2::write::GzEncoder;
+        use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+        use std::io::Write;
+
+        let data = std::fs::read("input/d01-f.txt").unwrap();
+
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&data).unwrap();
+        let gz = gz.finish().unwrap();
+        assert!(gz.starts_with(GZIP_MAGIC));
+        assert_eq!(decompress(&gz), data);
+
+        let zst = compress_to_vec(data.as_slice(), CompressionLevel::Fastest);
+        assert!(zst.starts_with(ZSTD_MAGIC));
+        assert_eq!(decompress(&zst), data);
+
+        let frames = [b"1 2\n".as_slice(), b"3 4\n"]
+            .map(|frame| compress_to_vec(frame, CompressionLevel::Fastest))
+            .concat();
+        assert_eq!(decompress(&frames), b"1 2\n3 4\n");
+
+        let mut members = Vec::new();
+        for frame in [b"1 2\n".as_slice(), b"3 4\n"] {
+            let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            gz.write_all(frame).unwrap();
+            members.extend(gz.finish().unwrap());
+        }
+        assert_eq!(decompress(&members), b"1 2\n3 4\n");
+
+        assert_eq!(decompress(&data), data);
+        assert_eq!(decompress(b"\x1f"), b"\x1f");
+        assert_eq!(decompress(b""), b"");
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut data = "\u{feff}a\r\n\r\nb \r\n  \r\n\r\n".as_bytes();
+        assert_eq!(lines(&mut data).collect::<Vec<String>>(), ["a", "", "b "]);
+
+        let mut empty = "\r\n\n".as_bytes();
+        assert_eq!(lines(&mut empty).count(), 0);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut data = "\u{feff}1\r\n2\r3\n\n".as_bytes();
+        assert_eq!(bytes(&mut data), b"1\n2\r3");
+    }
+
+    #[test]
+    fn test_crlf_inputs() {
+        let mut paths = std::fs::read_dir("input")
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let data = std::fs::read(&path).unwrap();
+            let converted = crlf(&data);
+
+            assert_eq!(
+                lines(&mut converted.as_slice()).collect::<Vec<String>>(),
+                lines(&mut data.as_slice()).collect::<Vec<String>>(),
+                "{path:?}"
+            );
+            assert_eq!(
+                grid(&mut converted.as_slice()),
+                grid(&mut data.as_slice()),
+                "{path:?}"
+            );
+            assert_eq!(
+                bytes(&mut converted.as_slice()),
+                bytes(&mut data.as_slice()),
+                "{path:?}"
+            );
+            assert!(!bytes(&mut data.as_slice()).contains(&b'\r'), "{path:?}");
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Repeatable pseudo-random numbers for the randomised tests
+
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        assert!(seed != 0, "a zero seed would only ever give zeroes");
+        XorShift(seed.wrapping_mul(0x9e37_79b9))
+    }
+}
+
+impl Iterator for XorShift {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        Some(self.0)
+    }
+}